@@ -0,0 +1,117 @@
+use crate::Point3;
+use crate::Ray;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Aabb {
+            min: a.inf(&b),
+            max: a.sup(&b),
+        }
+    }
+
+    pub fn empty() -> Self {
+        Aabb {
+            min: Point3::repeat(f64::INFINITY),
+            max: Point3::repeat(f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn infinite() -> Self {
+        Aabb {
+            min: Point3::repeat(f64::NEG_INFINITY),
+            max: Point3::repeat(f64::INFINITY),
+        }
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3>) -> Self {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |aabb, p| aabb.union(&Aabb::new(*p, *p)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min
+            .iter()
+            .chain(self.max.iter())
+            .all(|c| c.is_finite())
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.sup(&other.min),
+            max: self.max.inf(&other.max),
+        }
+    }
+
+    pub fn padded(&self, delta: f64) -> Aabb {
+        Aabb {
+            min: self.min - Point3::repeat(delta),
+            max: self.max + Point3::repeat(delta),
+        }
+    }
+
+    pub fn center(&self) -> Point3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // Returns the part of `t_range` where the ray is inside the box.
+    pub fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<std::ops::Range<f64>> {
+        let mut t_min = t_range.start;
+        let mut t_max = t_range.end;
+        for axis in 0..3 {
+            let inv_d = ray.direction()[axis].recip();
+            let t0 = (self.min[axis] - ray.origin()[axis]) * inv_d;
+            let t1 = (self.max[axis] - ray.origin()[axis]) * inv_d;
+            let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
+            // Comparisons with NaN are false, so a ray lying exactly on a slab
+            // boundary does not clip the range.
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some(t_min..t_max)
+    }
+}
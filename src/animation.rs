@@ -0,0 +1,304 @@
+use crate::{
+    Point3, Rc, Vec3,
+    camera::{Camera, CameraSettings, ImageSettings, QualitySettings},
+    hittable::Hittable,
+    scene::Scene,
+    transform::{Matrix4, Transformed},
+};
+use log::info;
+use std::ops::{Add, Mul, Range, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    // Smooth curve through all keys, with tangents from the neighbouring keys
+    CatmullRom,
+}
+
+// Values keyed by time in seconds, held at the first and last key outside of
+// the keyed range.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<(f64, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T> Track<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>,
+{
+    pub fn new(interpolation: Interpolation) -> Self {
+        Track {
+            keys: Vec::new(),
+            interpolation,
+        }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(Interpolation::Linear).with_key(0.0, value)
+    }
+
+    pub fn with_key(mut self, time: f64, value: T) -> Self {
+        let index = self.keys.partition_point(|(t, _)| *t <= time);
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    pub fn sample(&self, time: f64) -> T {
+        assert!(!self.keys.is_empty(), "track needs at least one key");
+        let last = self.keys.len() - 1;
+        let next = self.keys.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next > last {
+            return self.keys[last].1;
+        }
+
+        let (t0, p0) = self.keys[next - 1];
+        let (t1, p1) = self.keys[next];
+        let duration = t1 - t0;
+        let s = (time - t0) / duration;
+        match self.interpolation {
+            Interpolation::Linear => p0 + (p1 - p0) * s,
+            Interpolation::CatmullRom => {
+                // Cubic Hermite segment with tangents per second, so uneven
+                // key spacing does not cause jumps in speed
+                let tangent = |i: usize| {
+                    let (before, after) = (i.saturating_sub(1), (i + 1).min(last));
+                    let (ta, pa) = self.keys[before];
+                    let (tb, pb) = self.keys[after];
+                    (pb - pa) * (tb - ta).recip()
+                };
+                let (m0, m1) = (tangent(next - 1), tangent(next));
+                let (s2, s3) = (s * s, s * s * s);
+                p0 * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + m0 * ((s3 - 2.0 * s2 + s) * duration)
+                    + p1 * (-2.0 * s3 + 3.0 * s2)
+                    + m1 * ((s3 - s2) * duration)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CameraPath {
+    Keyframes(Track<Point3>),
+    // A horizontal circle around `center`, `height` above it, starting on
+    // the +x side and turning counterclockwise seen from above
+    Orbit {
+        center: Point3,
+        radius: f64,
+        height: f64,
+        // Seconds per revolution
+        period: f64,
+    },
+}
+
+impl CameraPath {
+    pub fn position(&self, time: f64) -> Point3 {
+        match self {
+            CameraPath::Keyframes(track) => track.sample(time),
+            CameraPath::Orbit {
+                center,
+                radius,
+                height,
+                period,
+            } => {
+                let angle = 2.0 * std::f64::consts::PI * time / period;
+                center + Vec3::new(radius * angle.cos(), *height, -radius * angle.sin())
+            }
+        }
+    }
+}
+
+// Animated camera settings. Everything left at None keeps the value of the
+// base settings.
+#[derive(Debug, Clone, Default)]
+pub struct CameraAnimation {
+    pub position: Option<CameraPath>,
+    pub lookat: Option<Track<Point3>>,
+    pub vfov: Option<Track<f64>>,
+    pub focus_dist: Option<Track<f64>>,
+}
+
+impl CameraAnimation {
+    // Circles around `center` while looking at it.
+    pub fn orbit(center: Point3, radius: f64, height: f64, period: f64) -> Self {
+        CameraAnimation {
+            position: Some(CameraPath::Orbit {
+                center,
+                radius,
+                height,
+                period,
+            }),
+            lookat: Some(Track::constant(center)),
+            ..Default::default()
+        }
+    }
+
+    pub fn settings_at(&self, settings: &CameraSettings, time: f64) -> CameraSettings {
+        let mut settings = settings.clone();
+        if let Some(position) = &self.position {
+            settings.camera_center = position.position(time);
+        }
+        if let Some(lookat) = &self.lookat {
+            settings.camera_lookat = lookat.sample(time);
+        }
+        if let Some(vfov) = &self.vfov {
+            settings.vfov = vfov.sample(time);
+        }
+        if let Some(focus_dist) = &self.focus_dist {
+            settings.focus_dist = focus_dist.sample(time);
+        }
+        settings
+    }
+}
+
+// Object transform keyed over time, applied as scale, then rotation by
+// Euler angles in degrees around x, y and z, then translation.
+#[derive(Debug, Clone, Default)]
+pub struct TransformAnimation {
+    pub translation: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Vec3>>,
+    pub scale: Option<Track<Vec3>>,
+}
+
+impl TransformAnimation {
+    pub fn matrix_at(&self, time: f64) -> Matrix4 {
+        let translation = self
+            .translation
+            .as_ref()
+            .map_or(Vec3::zeros(), |t| t.sample(time));
+        let rotation = self
+            .rotation
+            .as_ref()
+            .map_or(Vec3::zeros(), |r| r.sample(time).map(f64::to_radians));
+        let scale = self
+            .scale
+            .as_ref()
+            .map_or(Vec3::new(1.0, 1.0, 1.0), |s| s.sample(time));
+        Matrix4::new_translation(&translation)
+            * nalgebra::Rotation3::from_euler_angles(rotation.x, rotation.y, rotation.z)
+                .to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&scale)
+    }
+}
+
+// An object moving over time. The world of each frame places it with `at`.
+#[derive(Debug, Clone)]
+pub struct Animated {
+    object: Rc<dyn Hittable>,
+    animation: TransformAnimation,
+}
+
+impl Animated {
+    pub fn new<T: Hittable + 'static>(object: T, animation: TransformAnimation) -> Self {
+        Animated {
+            object: Rc::new(object),
+            animation,
+        }
+    }
+
+    pub fn at(&self, time: f64) -> Transformed {
+        Transformed::from_rc(self.object.clone(), self.animation.matrix_at(time))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationSettings {
+    pub fps: f64,
+    pub frames: Range<usize>,
+    // File name of each frame with `{frame}` replaced by the zero padded
+    // frame number
+    pub output_pattern: String,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        AnimationSettings {
+            fps: 24.0,
+            frames: 0..48,
+            output_pattern: "frame_{frame}.png".to_string(),
+        }
+    }
+}
+
+impl AnimationSettings {
+    pub fn time(&self, frame: usize) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    pub fn frame_path(&self, frame: usize) -> String {
+        self.output_pattern
+            .replace("{frame}", &format!("{frame:04}"))
+    }
+}
+
+// Renders each frame of the range to its numbered file. `scene_at` builds the
+// scene at a time in seconds. Without the image feature the frames are
+// written as PPM.
+pub fn render_frames<W: Hittable>(
+    animation: &AnimationSettings,
+    image_settings: ImageSettings,
+    quality_settings: QualitySettings,
+    camera_settings: &CameraSettings,
+    camera_animation: &CameraAnimation,
+    scene_at: impl FnMut(f64) -> Scene<W>,
+) -> std::io::Result<()> {
+    FrameSequence {
+        animation,
+        image_settings,
+        quality_settings,
+        camera_settings,
+        camera_animation,
+    }
+    .render(scene_at, |frame, camera, scene| {
+        let path = animation.frame_path(frame);
+        info!("Writing {path}");
+
+        #[cfg(feature = "image")]
+        camera
+            .render_to_imgbuf(scene)
+            .save(&path)
+            .map_err(std::io::Error::other)?;
+
+        #[cfg(not(feature = "image"))]
+        camera.render(
+            &mut std::io::BufWriter::new(std::fs::File::create(&path)?),
+            scene,
+        )?;
+        Ok(())
+    })
+}
+
+// Everything needed to set up the camera of each frame.
+#[derive(Debug, Clone)]
+pub struct FrameSequence<'a> {
+    pub animation: &'a AnimationSettings,
+    pub image_settings: ImageSettings,
+    pub quality_settings: QualitySettings,
+    pub camera_settings: &'a CameraSettings,
+    pub camera_animation: &'a CameraAnimation,
+}
+
+impl FrameSequence<'_> {
+    // Calls `output` with the frame number, camera and scene of every frame.
+    pub fn render<W: Hittable>(
+        &self,
+        mut scene_at: impl FnMut(f64) -> Scene<W>,
+        mut output: impl FnMut(usize, &Camera, &Scene<W>) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        for frame in self.animation.frames.clone() {
+            let time = self.animation.time(frame);
+            info!("Rendering frame {frame} at {time:.3}s");
+
+            let settings = self
+                .camera_animation
+                .settings_at(self.camera_settings, time);
+            let camera = Camera::new(self.image_settings, self.quality_settings, settings);
+            output(frame, &camera, &scene_at(time))?;
+        }
+        Ok(())
+    }
+}
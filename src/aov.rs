@@ -0,0 +1,235 @@
+use crate::{Ray, Rc, Vec3, color::Color, hittable::HitRecord, material::Material, medium::Medium};
+use std::io::Write;
+
+// Arbitrary output variables, extra passes of a render for compositing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    // The beauty pass, linear
+    Color,
+    // Light reaching the camera after at most one bounce, including visible
+    // emitters and the background
+    Direct,
+    // Color minus direct
+    Indirect,
+    // Distance from the camera along the ray through the pixel center,
+    // infinite for the background
+    Depth,
+    // World space shading normal of the first hit
+    Normal,
+    // Attenuation of the first bounce
+    Albedo,
+    // 1 based index of the hit object in the world list, 0 for the background
+    ObjectId,
+    // ID of the hit material, see `Tagged`, 0 for the background and
+    // untagged materials
+    MaterialId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 8] = [
+        Aov::Color,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Color => "color",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+        }
+    }
+
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Color | Aov::Direct | Aov::Indirect | Aov::Albedo => &["R", "G", "B"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::ObjectId | Aov::MaterialId => &["ID"],
+        }
+    }
+}
+
+// Gives a material an ID for the material ID pass, the same in every render
+// of the scene.
+#[derive(Debug, Clone)]
+pub struct Tagged {
+    inner: Rc<dyn Material>,
+    id: usize,
+}
+
+impl Tagged {
+    pub fn new(inner: Rc<dyn Material>, id: usize) -> Self {
+        Tagged { inner, id }
+    }
+}
+
+impl Material for Tagged {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        self.inner.scatter(ray_in, hit_record)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        self.inner.eval(ray_in, hit_record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        self.inner.pdf(ray_in, hit_record, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.inner.emitted(ray_in, hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.inner.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.inner.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        self.inner.is_transparent(hit_record)
+    }
+
+    fn id(&self, _hit_record: &HitRecord) -> usize {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct AovPixel {
+    pub color: Color,
+    pub direct: Color,
+    pub indirect: Color,
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub object_id: usize,
+    pub material_id: usize,
+}
+
+impl AovPixel {
+    fn values(&self, aov: Aov) -> Vec<f32> {
+        let vector = |v: &Vec3| vec![v.x as f32, v.y as f32, v.z as f32];
+        match aov {
+            Aov::Color => vector(&self.color),
+            Aov::Direct => vector(&self.direct),
+            Aov::Indirect => vector(&self.indirect),
+            Aov::Depth => vec![self.depth as f32],
+            Aov::Normal => vector(&self.normal),
+            Aov::Albedo => vector(&self.albedo),
+            Aov::ObjectId => vec![self.object_id as f32],
+            Aov::MaterialId => vec![self.material_id as f32],
+        }
+    }
+}
+
+// A multi-channel framebuffer with one layer per AOV, each holding the
+// channels of a pixel next to each other, row by row from the top.
+#[derive(Debug, Clone)]
+pub struct AovBuffer {
+    width: usize,
+    height: usize,
+    layers: Vec<(Aov, Vec<f32>)>,
+}
+
+impl AovBuffer {
+    pub(crate) fn from_pixels(
+        width: usize,
+        height: usize,
+        aovs: &[Aov],
+        pixels: &[AovPixel],
+    ) -> Self {
+        let layers = aovs
+            .iter()
+            .map(|&aov| (aov, pixels.iter().flat_map(|p| p.values(aov)).collect()))
+            .collect();
+        AovBuffer {
+            width,
+            height,
+            layers,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn layer(&self, aov: Aov) -> Option<&[f32]> {
+        self.layers
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    // Writes every layer to `{prefix}.{name}.pfm`, the uncompressed portable
+    // float map format.
+    pub fn save_separate(&self, prefix: &str) -> std::io::Result<()> {
+        for (aov, data) in &self.layers {
+            let path = format!("{prefix}.{}.pfm", aov.name());
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let kind = if aov.channels().len() == 1 {
+                "Pf"
+            } else {
+                "PF"
+            };
+            // A negative scale marks little endian data
+            write!(file, "{kind}\n{} {}\n-1.0\n", self.width, self.height)?;
+            let row_len = self.width * aov.channels().len();
+            // Rows go from the bottom up
+            for row in data.chunks(row_len).rev() {
+                for value in row {
+                    file.write_all(&value.to_le_bytes())?;
+                }
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    // Writes all layers into one OpenEXR file. The color layer becomes the
+    // main R, G and B channels, the others are named like `direct.R`.
+    #[cfg(feature = "image")]
+    pub fn save_exr<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        use exr::prelude::*;
+
+        let mut channels = SmallVec::new();
+        for (aov, data) in &self.layers {
+            let count = aov.channels().len();
+            for (i, channel) in aov.channels().iter().enumerate() {
+                let name = match aov {
+                    Aov::Color => channel.to_string(),
+                    _ => format!("{}.{channel}", aov.name()),
+                };
+                let samples = data.iter().skip(i).step_by(count).copied().collect();
+                channels.push(AnyChannel::new(name.as_str(), FlatSamples::F32(samples)));
+            }
+        }
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(std::io::Error::other)
+    }
+}
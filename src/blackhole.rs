@@ -0,0 +1,113 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::material::{Black, BlackHoleLayer, BlackHoleLayerSettings, Material};
+use crate::sphere::Sphere;
+
+#[derive(Debug, Clone)]
+pub struct BlackHoleSettings {
+    pub layer_count: usize,
+    // Layer i sits at inner_radius * (1 + (profile_extent * i / layer_count)
+    // ^ profile_exponent), times the scale of the black hole.
+    pub inner_radius: f64,
+    pub profile_extent: f64,
+    pub profile_exponent: f64,
+    pub layer: BlackHoleLayerSettings,
+    // In world units, not scaled with the black hole
+    pub horizon_radius: f64,
+    pub horizon_material: Rc<dyn Material>,
+}
+
+impl Default for BlackHoleSettings {
+    fn default() -> Self {
+        BlackHoleSettings {
+            layer_count: 64,
+            inner_radius: 1.0 / 40.0,
+            profile_extent: 4.25,
+            profile_exponent: 2.5,
+            layer: BlackHoleLayerSettings::default(),
+            horizon_radius: 0.01,
+            horizon_material: Rc::new(Black::new()),
+        }
+    }
+}
+
+// Gravitational lensing faked with concentric spheres whose index of
+// refraction grows towards the event horizon.
+#[derive(Debug)]
+pub struct BlackHole {
+    center: Point3,
+    radius: f64,
+    layers: HittableList,
+}
+
+impl BlackHole {
+    pub fn new(center: Point3, scale: f64, settings: BlackHoleSettings) -> Self {
+        let mut layers = HittableList::new();
+        let mut radius: f64 = 0.0;
+        let layer_count = settings.layer_count as f64;
+
+        for layer_index in 0..settings.layer_count {
+            let profile = (layer_index as f64 * settings.profile_extent / layer_count)
+                .powf(settings.profile_exponent)
+                + 1.0;
+            let layer_radius = profile * settings.inner_radius * scale;
+            radius = radius.max(layer_radius);
+            layers.push(Sphere::new(
+                center,
+                layer_radius,
+                Rc::new(BlackHoleLayer::with_settings(
+                    profile,
+                    layer_count,
+                    settings.layer,
+                )),
+            ));
+        }
+
+        let horizon_radius = settings.horizon_radius;
+        radius = radius.max(horizon_radius);
+        layers.push(Sphere::new(
+            center,
+            horizon_radius,
+            settings.horizon_material.clone(),
+        ));
+
+        BlackHole {
+            center,
+            radius,
+            layers,
+        }
+    }
+
+    // Whether the ray comes within the outermost layer during `t_range`.
+    fn hits_bounding_sphere(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> bool {
+        let oc = self.center - ray.origin();
+        let a = ray.direction().magnitude_squared();
+        let h = oc.dot(&ray.direction());
+        let c = oc.magnitude_squared() - self.radius * self.radius;
+        let discriminant = h * h - a * c;
+        if discriminant < 0.0 {
+            return false;
+        }
+        let sqrt_d = discriminant.sqrt();
+        (h - sqrt_d) / a < t_range.end && (h + sqrt_d) / a >= t_range.start
+    }
+}
+
+impl Hittable for BlackHole {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        if !self.hits_bounding_sphere(ray, t_range) {
+            return None;
+        }
+        self.layers.hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::repeat(self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+}
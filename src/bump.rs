@@ -0,0 +1,230 @@
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::medium::Medium;
+use crate::texture::Texture;
+use crate::{Ray, Rc, Vec3};
+
+// Perturbs the shading normal of another material with a tangent space
+// normal map, decoded from linear RGB in 0..1 with +y along dpdv. Images
+// should be loaded with `ImageTexture::load_linear`.
+#[derive(Debug, Clone)]
+pub struct NormalMap {
+    inner: Rc<dyn Material>,
+    map: Rc<dyn Texture>,
+    strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(inner: Rc<dyn Material>, map: Rc<dyn Texture>, strength: f64) -> Self {
+        NormalMap {
+            inner,
+            map,
+            strength,
+        }
+    }
+
+    fn shading_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        let (dpdu, dpdv) = hit_record.tangents();
+        let n = hit_record.normal;
+        let t = (dpdu - n * n.dot(&dpdu)).normalize();
+        let b = dpdv - n * n.dot(&dpdv) - t * t.dot(&dpdv);
+        let b = if b.norm_squared() > 0.0 {
+            b.normalize()
+        } else {
+            n.cross(&t)
+        };
+
+        let encoded = self.map.value(hit_record.u, hit_record.v, &hit_record.p);
+        let local = encoded * 2.0 - Color::repeat(1.0);
+        let perturbed = t * local.x * self.strength + b * local.y * self.strength + n * local.z;
+        if perturbed.norm_squared() > 0.0 {
+            perturbed.normalize()
+        } else {
+            n
+        }
+    }
+}
+
+// Perturbs the shading normal of another material as if the surface was
+// displaced along its normal by the first channel of the height texture
+// times `scale`, using finite differences along the surface.
+#[derive(Debug, Clone)]
+pub struct BumpMap {
+    inner: Rc<dyn Material>,
+    height: Rc<dyn Texture>,
+    scale: f64,
+}
+
+// Step in uv, or in world units when the shape has no parameterization
+const BUMP_DELTA: f64 = 5e-4;
+
+impl BumpMap {
+    pub fn new(inner: Rc<dyn Material>, height: Rc<dyn Texture>, scale: f64) -> Self {
+        BumpMap {
+            inner,
+            height,
+            scale,
+        }
+    }
+
+    fn shading_normal(&self, hit_record: &HitRecord) -> Vec3 {
+        let (dpdu, dpdv) = hit_record.tangents();
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let height = |u: f64, v: f64, p: &Vec3| self.height.value(u, v, p).x * self.scale;
+
+        let h = height(u, v, &p);
+        let dhdu = (height(u + BUMP_DELTA, v, &(p + dpdu * BUMP_DELTA)) - h) / BUMP_DELTA;
+        let dhdv = (height(u, v + BUMP_DELTA, &(p + dpdv * BUMP_DELTA)) - h) / BUMP_DELTA;
+
+        let n = hit_record.normal;
+        let perturbed = (dpdu + n * dhdu).cross(&(dpdv + n * dhdv));
+        if perturbed.norm_squared() == 0.0 {
+            return n;
+        }
+        let perturbed = perturbed.normalize();
+        if perturbed.dot(&n) < 0.0 {
+            -perturbed
+        } else {
+            perturbed
+        }
+    }
+}
+
+// Replaces the normal of the hit with the shading normal, bent towards the
+// viewer if it would face away from it.
+fn shaded(ray_in: &Ray, hit_record: &HitRecord, shading_normal: Vec3) -> HitRecord {
+    const MIN_COS: f64 = 1e-2;
+    let wo = -ray_in.direction().normalize();
+    let cos = shading_normal.dot(&wo);
+    let normal = if cos < MIN_COS {
+        (shading_normal + wo * (MIN_COS - cos)).normalize()
+    } else {
+        shading_normal
+    };
+    HitRecord {
+        normal,
+        ..hit_record.clone()
+    }
+}
+
+// Directions on opposite sides of the geometric and shading surfaces would
+// let light leak through the surface or reflect into it.
+fn is_consistent(hit_record: &HitRecord, shaded: &HitRecord, direction: &Vec3) -> bool {
+    hit_record.normal.dot(direction) * shaded.normal.dot(direction) > 0.0
+}
+
+fn scatter_shaded(
+    inner: &dyn Material,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    shaded: &HitRecord,
+) -> Option<(Color, Ray)> {
+    let (attenuation, scattered) = inner.scatter(ray_in, shaded)?;
+    is_consistent(hit_record, shaded, &scattered.direction()).then_some((attenuation, scattered))
+}
+
+fn eval_shaded(
+    inner: &dyn Material,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    shaded: &HitRecord,
+    direction: &Vec3,
+) -> Option<Color> {
+    let f = inner.eval(ray_in, shaded, direction)?;
+    Some(if is_consistent(hit_record, shaded, direction) {
+        f
+    } else {
+        Color::zeros()
+    })
+}
+
+// Directions scatter never picks have no density, which keeps the MIS
+// weights in line with eval.
+fn pdf_shaded(
+    inner: &dyn Material,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    shaded: &HitRecord,
+    direction: &Vec3,
+) -> f64 {
+    if is_consistent(hit_record, shaded, direction) {
+        inner.pdf(ray_in, shaded, direction)
+    } else {
+        0.0
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        scatter_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        eval_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        pdf_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.inner.emitted(ray_in, hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.inner.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.inner.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        self.inner.is_transparent(hit_record)
+    }
+
+    fn id(&self, hit_record: &HitRecord) -> usize {
+        self.inner.id(hit_record)
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        scatter_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        eval_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let shaded = shaded(ray_in, hit_record, self.shading_normal(hit_record));
+        pdf_shaded(self.inner.as_ref(), ray_in, hit_record, &shaded, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.inner.emitted(ray_in, hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.inner.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.inner.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        self.inner.is_transparent(hit_record)
+    }
+
+    fn id(&self, hit_record: &HitRecord) -> usize {
+        self.inner.id(hit_record)
+    }
+}
@@ -1,12 +1,16 @@
 use crate::{
     Point3, Ray, Vec3,
+    aov::{Aov, AovBuffer, AovPixel},
     color::{Color, write_color},
-    hittable::Hittable,
-    random_vec3_in_unit_disk,
+    hittable::{HitRecord, Hittable, hit_opaque},
+    lens::{Aperture, FStop, LensSystem},
+    medium::{beer_lambert, sample_henyey_greenstein},
+    near_zero, orthonormal_basis,
+    scene::Scene,
+    spectrum::{Wavelengths, rgb_to_spectral, spectral_to_rgb},
 };
-use log::info;
+use log::{info, warn};
 use rand::Rng;
-use std::io::stdout;
 
 #[derive(Debug, Clone, Copy)]
 pub struct ImageSettings {
@@ -18,16 +22,134 @@ pub struct ImageSettings {
 pub struct QualitySettings {
     pub samples_per_pixel: usize,
     pub max_depth: usize,
+    // Sample the environment directly at every diffuse bounce. This cuts down
+    // noise from bright, small regions of environment maps but shadow rays
+    // can't see through glass, portals or black holes.
+    pub light_sampling: bool,
+    // Trace wavelengths instead of RGB colors, needed for dispersion. Each
+    // path carries three wavelengths, RGB inputs are upsampled to spectra.
+    pub spectral: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeMapping {
+    // Distance from the image center proportional to the angle
+    Equidistant,
+    // Equal areas on the image cover equal solid angles
+    Equisolid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubeFace {
+    Front,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // Thin lens perspective using vfov, focus_dist and defocus_angle, or
+    // the lens system if there is one
+    Perspective,
+    // Parallel rays over a view `height` world units tall
+    Orthographic { height: f64 },
+    // A circular image covering `fov` degrees across its height, black
+    // outside of the circle
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    // Full 360x180 degree panorama centered on the look direction, for 2:1
+    // images
+    Equirectangular,
+    // One 90 degree face of a cube map, for square images
+    CubeFace(CubeFace),
+}
+
+#[derive(Debug, Clone)]
 pub struct CameraSettings {
+    pub projection: Projection,
     pub vfov: f64,
     pub focus_dist: f64,
     pub defocus_angle: f64,
     pub camera_center: Point3,
     pub camera_lookat: Point3,
     pub camera_vup: Vec3,
+    // Shape of the bokeh, for the thin lens and the stop of lens systems
+    pub aperture: Aperture,
+    // Traces perspective rays through real lens elements instead of the thin
+    // lens, with the field of view following from the sensor size
+    pub lens_system: Option<LensSystem>,
+    // Replaces vfov and defocus_angle of the thin lens, or sets the stop of
+    // the lens system
+    pub f_stop: Option<FStop>,
+}
+
+impl CameraSettings {
+    // Sets focus_dist to the distance of the surface seen through the center
+    // of `pixel`, or of the image by default. Surfaces that let rays through
+    // without bending them, like boundaries between media of the same
+    // refraction index or the outer layers of a black hole, are looked
+    // through. Anything else is focused on, including glass and portals.
+    pub fn with_autofocus<W: Hittable>(
+        mut self,
+        image_settings: ImageSettings,
+        world: &W,
+        pixel: Option<(usize, usize)>,
+    ) -> Self {
+        let quality_settings = QualitySettings {
+            samples_per_pixel: 1,
+            max_depth: 1,
+            light_sampling: false,
+            spectral: false,
+        };
+        let vfov = match (&self.lens_system, self.f_stop) {
+            (Some(lens), _) => lens.vfov(),
+            (None, Some(f_stop)) => f_stop.vfov(),
+            (None, None) => self.vfov,
+        };
+        let pinhole = CameraSettings {
+            vfov,
+            defocus_angle: 0.0,
+            lens_system: None,
+            f_stop: None,
+            ..self.clone()
+        };
+        let probe = Camera::new(image_settings, quality_settings, pinhole);
+        let (x, y) = pixel.map_or(
+            (
+                (probe.image_width as f64 - 1.0) / 2.0,
+                (probe.image_height as f64 - 1.0) / 2.0,
+            ),
+            |(x, y)| (x as f64, y as f64),
+        );
+        let hit = probe.ray_through(x, y).and_then(|ray| {
+            let mut start = 0.001;
+            loop {
+                let hit = hit_opaque(world, &ray, &(start..f64::INFINITY))?;
+                if !passes_straight_through(&ray, &hit) {
+                    return Some((ray, hit));
+                }
+                start = hit.t + 1e-9 * hit.t.abs().max(1.0);
+            }
+        });
+        match hit {
+            Some((ray, hit)) => {
+                // Along the view direction, as the focus plane faces the camera
+                let forward = (self.camera_lookat - self.camera_center).normalize();
+                self.focus_dist = (hit.p - ray.origin()).dot(&forward).abs();
+                info!(
+                    "Autofocus: focus distance {:.4} through pixel ({x}, {y})",
+                    self.focus_dist
+                );
+            }
+            None => warn!(
+                "Autofocus: nothing visible through pixel ({x}, {y}), keeping focus distance {}",
+                self.focus_dist
+            ),
+        }
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,14 +157,27 @@ pub struct Camera {
     image_width: usize,
     image_height: usize,
     camera_center: Point3,
+    projection: Projection,
+    // Camera frame: right, up and backwards
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
     pixel00_loc: Point3,
     samples_per_pixel: usize,
     max_depth: usize,
+    light_sampling: bool,
+    spectral: bool,
     defocus_angle: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    aperture: Aperture,
+    lens_system: Option<LensSystem>,
+    // Brings the sensor center of lens systems to full brightness
+    exposure: f64,
+    // Signed eye offset for omni-directional stereo panoramas
+    eye_offset: f64,
 }
 
 impl Camera {
@@ -54,8 +189,21 @@ impl Camera {
         let image_width = image_settings.image_width;
         let aspect_ratio = image_settings.aspect_ratio;
         let image_height = (image_width as f64 / aspect_ratio).max(1.0) as usize;
-        let viewport_height =
-            2.0 * (camera_settings.focus_dist * (camera_settings.vfov.to_radians() / 2.0).tan());
+
+        let (vfov, defocus_angle) = match camera_settings.f_stop {
+            Some(f_stop) if camera_settings.lens_system.is_none() => {
+                let vfov = f_stop.vfov();
+                let defocus_angle = f_stop.defocus_angle(camera_settings.focus_dist);
+                info!(
+                    "f/{} at {}mm: vfov {vfov:.2} degrees, defocus angle {defocus_angle:.4} degrees",
+                    f_stop.f_number, f_stop.focal_length
+                );
+                (vfov, defocus_angle)
+            }
+            _ => (camera_settings.vfov, camera_settings.defocus_angle),
+        };
+
+        let viewport_height = 2.0 * (camera_settings.focus_dist * (vfov.to_radians() / 2.0).tan());
         let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
 
         let w = (camera_settings.camera_center - camera_settings.camera_lookat).normalize();
@@ -73,61 +221,152 @@ impl Camera {
             - viewport_v / 2.0;
         let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
 
-        let defocus_radius =
-            (camera_settings.defocus_angle / 2.0).to_radians().tan() * camera_settings.focus_dist;
+        let defocus_radius = (defocus_angle / 2.0).to_radians().tan() * camera_settings.focus_dist;
         let defocus_disk_u = u * defocus_radius;
         let defocus_disk_v = v * defocus_radius;
 
+        let mut lens_system = camera_settings.lens_system;
+        let mut exposure = 1.0;
+        if let Some(lens) = &mut lens_system {
+            if let Some(f_stop) = camera_settings.f_stop {
+                *lens = lens.clone().with_f_number(f_stop.f_number);
+                info!(
+                    "f/{}: stop diameter {:.2}mm",
+                    f_stop.f_number, lens.stop_diameter
+                );
+            }
+            lens.focus(camera_settings.focus_dist / lens.scale);
+            let transmission = lens.center_transmission(&camera_settings.aperture);
+            if transmission > 0.0 {
+                exposure = transmission.recip();
+            } else {
+                warn!("No light from the sensor center passes the lens, exposure left at 1");
+            }
+            info!(
+                "Lens system: focal length {:.1}mm, sensor at {:.2}mm behind the rear element",
+                lens.focal_length(),
+                lens.elements().last().map_or(0.0, |e| e.thickness)
+            );
+        }
+
         Camera {
             image_width,
             image_height,
             camera_center: camera_settings.camera_center,
+            projection: camera_settings.projection,
+            u,
+            v,
+            w,
             pixel_delta_u,
             pixel_delta_v,
             pixel00_loc,
             samples_per_pixel: quality_settings.samples_per_pixel,
             max_depth: quality_settings.max_depth,
-            defocus_angle: camera_settings.defocus_angle,
+            light_sampling: quality_settings.light_sampling,
+            spectral: quality_settings.spectral,
+            defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            aperture: camera_settings.aperture,
+            lens_system,
+            exposure,
+            eye_offset: 0.0,
         }
     }
 
+    // Offsets each equirectangular ray sideways along the viewing circle by
+    // `eye_offset`, negative for the left eye.
+    pub(crate) fn with_eye_offset(mut self, eye_offset: f64) -> Self {
+        self.eye_offset = eye_offset;
+        self
+    }
+
+    pub fn image_width(&self) -> usize {
+        self.image_width
+    }
+
+    pub fn image_height(&self) -> usize {
+        self.image_height
+    }
+
+    // The averaged color of all samples of a pixel.
+    pub(crate) fn pixel_color<W: Hittable>(&self, x: usize, y: usize, scene: &Scene<W>) -> Color {
+        #[cfg(feature = "rayon")]
+        use rayon::prelude::*;
+        #[cfg(feature = "rayon")]
+        let sample_iter = (0..self.samples_per_pixel).into_par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let sample_iter = 0..self.samples_per_pixel;
+
+        let color: Color = sample_iter.map(|_| self.sample_pixel(x, y, scene)).sum();
+        color / self.samples_per_pixel as f64
+    }
+
     pub fn render<T: std::io::Write, W: Hittable>(
         &self,
         file: &mut T,
-        world: &W,
+        scene: &Scene<W>,
     ) -> std::io::Result<()> {
-        #[cfg(feature = "rayon")]
-        use rayon::prelude::*;
-        let pixel_samples_scale = (self.samples_per_pixel as f64).recip();
-        writeln!(file, "P3\n{} {}", self.image_width, self.image_height)?;
-        println!("255");
+        writeln!(file, "P3\n{} {}\n255", self.image_width, self.image_height)?;
         for y in 0..self.image_height {
             info!("Scanlines remaining: {}", self.image_height - y);
             for x in 0..self.image_width {
-                #[cfg(feature = "rayon")]
-                let sample_iter = (0..self.samples_per_pixel).into_par_iter();
-                #[cfg(not(feature = "rayon"))]
-                let sample_iter = 0..self.samples_per_pixel;
-
-                let color: Color = sample_iter
-                    .map(|_| {
-                        let ray = self.get_ray(x, y);
-                        ray_color(&ray, world, self.max_depth)
-                    })
-                    .sum();
-                write_color(&mut stdout(), color * pixel_samples_scale)?;
+                write_color(file, self.pixel_color(x, y, scene))?;
             }
         }
         info!("Done.");
         Ok(())
     }
 
+    // Renders the requested AOVs into a multi-channel framebuffer.
+    pub fn render_aovs<W: Hittable>(&self, scene: &Scene<W>, aovs: &[Aov]) -> AovBuffer {
+        #[cfg(feature = "rayon")]
+        use indicatif::ParallelProgressIterator;
+        #[cfg(not(feature = "rayon"))]
+        use indicatif::ProgressIterator;
+        #[cfg(feature = "rayon")]
+        use rayon::prelude::*;
+
+        use indicatif::{ProgressFinish, ProgressStyle};
+        let progress_style = ProgressStyle::with_template(
+            "[{elapsed_precise}/{duration_precise}] {bar:80.green/white} {pos:>7}/{len:7} {msg}",
+        )
+        .unwrap()
+        .progress_chars("##-");
+
+        #[cfg(feature = "rayon")]
+        let pixel_iter = (0..self.image_width * self.image_height).into_par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let pixel_iter = 0..self.image_width * self.image_height;
+
+        let pixels: Vec<_> = pixel_iter
+            .progress_with_style(progress_style)
+            .with_finish(ProgressFinish::AndLeave)
+            .map(|i| self.aov_pixel(i % self.image_width, i / self.image_width, scene))
+            .collect();
+        info!("Done.");
+        AovBuffer::from_pixels(self.image_width, self.image_height, aovs, &pixels)
+    }
+
+    // Row by row 8 bit RGB pixels of the image.
+    pub fn render_to_rgb<W: Hittable>(&self, scene: &Scene<W>) -> Vec<u8> {
+        #[cfg(feature = "image")]
+        return self.render_to_imgbuf(scene).into_raw();
+
+        #[cfg(not(feature = "image"))]
+        (0..self.image_height)
+            .flat_map(|y| (0..self.image_width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let (r, g, b) = crate::color::color_to_rgb(self.pixel_color(x, y, scene));
+                [r, g, b]
+            })
+            .collect()
+    }
+
     #[cfg(feature = "image")]
     pub fn render_to_imgbuf<W: Hittable>(
         &self,
-        world: &W,
+        scene: &Scene<W>,
     ) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
         #[cfg(feature = "rayon")]
         use indicatif::ParallelProgressIterator;
@@ -158,12 +397,16 @@ impl Camera {
 
                 let color: Color = sample_iter
                     .map(|_| {
-                        let ray = self.get_ray(x as usize, y as usize);
-                        let color = ray_color(&ray, world, self.max_depth);
-                        debug_assert!(color.x >= 0.0 && color.y >= 0.0 && color.z >= 0.0);
-                        Color::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0))
+                        let color = self.sample_pixel(x as usize, y as usize, scene);
+                        // Single wavelength samples are often out of the sRGB
+                        // gamut, only their average has to be positive.
+                        debug_assert!(
+                            self.spectral || (color.x >= 0.0 && color.y >= 0.0 && color.z >= 0.0)
+                        );
+                        color
                     })
-                    .sum();
+                    .sum::<Color>()
+                    .sup(&Color::zeros());
                 let (r, g, b) = crate::color::color_to_rgb(color * pixel_samples_scale);
                 *pixel = image::Rgb([r, g, b]);
             });
@@ -173,42 +416,446 @@ impl Camera {
 }
 
 impl Camera {
-    fn get_ray(&self, x: usize, y: usize) -> Ray {
-        let (offset_x, offset_y) = (
+    // Returns None for pixels outside of the image circle of fisheye lenses.
+    fn get_ray(&self, x: usize, y: usize) -> Option<Ray> {
+        let (offset_x, offset_y): (f64, f64) = (
             rand::rng().random_range(-0.5..0.5),
             rand::rng().random_range(-0.5..0.5),
         );
+        self.ray_through(x as f64 + offset_x, y as f64 + offset_y)
+    }
 
-        let sample_center = self.pixel00_loc
-            + ((x as f64 + offset_x) * self.pixel_delta_u)
-            + ((y as f64 + offset_y) * self.pixel_delta_v);
-        let origin = if self.defocus_angle != 0.0 {
-            let p = random_vec3_in_unit_disk();
-            self.camera_center + p.x * self.defocus_disk_u + p.y * self.defocus_disk_v
+    // A ray through a position on the image in pixels, with pixel centers at
+    // whole numbers.
+    fn ray_through(&self, x: f64, y: f64) -> Option<Ray> {
+        // Position on the image in -1..1, y going up
+        let a = 2.0 * (x + 0.5) / self.image_width as f64 - 1.0;
+        let b = 1.0 - 2.0 * (y + 0.5) / self.image_height as f64;
+        let aspect_ratio = self.image_width as f64 / self.image_height as f64;
+
+        let (origin, dir) = match self.projection {
+            Projection::Perspective if self.lens_system.is_some() => {
+                let lens = self.lens_system.as_ref().unwrap();
+                // The image is upside down on the sensor
+                let sensor_pos = Point3::new(
+                    -a * lens.sensor_height * aspect_ratio / 2.0,
+                    -b * lens.sensor_height / 2.0,
+                    0.0,
+                );
+                let (origin, dir) = lens.sample_ray(sensor_pos, &self.aperture)?;
+                (
+                    self.camera_center + lens.scale * self.to_world(&origin),
+                    self.to_world(&dir),
+                )
+            }
+            Projection::Perspective => {
+                let sample_center =
+                    self.pixel00_loc + (x * self.pixel_delta_u) + (y * self.pixel_delta_v);
+                let origin = if self.defocus_angle != 0.0 {
+                    // Position on the image, 1 in the corners
+                    let diagonal = (aspect_ratio * aspect_ratio + 1.0).sqrt();
+                    let image_pos = (a * aspect_ratio / diagonal, b / diagonal);
+                    let (px, py) = self.aperture.sample(image_pos)?;
+                    self.camera_center + px * self.defocus_disk_u + py * self.defocus_disk_v
+                } else {
+                    self.camera_center
+                };
+                (origin, sample_center - origin)
+            }
+            Projection::Orthographic { height } => {
+                let origin = self.camera_center
+                    + self.u * (a * height * aspect_ratio / 2.0)
+                    + self.v * (b * height / 2.0);
+                (origin, -self.w)
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let (px, py) = (a * aspect_ratio, b);
+                let r = (px * px + py * py).sqrt();
+                let half_fov = fov.to_radians() / 2.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half_fov,
+                    FisheyeMapping::Equisolid => {
+                        let s = r * (half_fov / 2.0).sin();
+                        if s > 1.0 {
+                            return None;
+                        }
+                        2.0 * s.asin()
+                    }
+                };
+                if r > 1.0 || theta > std::f64::consts::PI {
+                    return None;
+                }
+                let (ux, uy) = if r > 0.0 {
+                    (px / r, py / r)
+                } else {
+                    (0.0, 0.0)
+                };
+                let local = Vec3::new(ux * theta.sin(), uy * theta.sin(), -theta.cos());
+                (self.camera_center, self.to_world(&local))
+            }
+            Projection::Equirectangular => {
+                let longitude = a * std::f64::consts::PI;
+                let latitude = b * std::f64::consts::FRAC_PI_2;
+                let local = Vec3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+                // Tangent to the viewing circle, perpendicular to the ray
+                let side = Vec3::new(longitude.cos(), 0.0, longitude.sin());
+                let origin = self.camera_center + self.eye_offset * self.to_world(&side);
+                (origin, self.to_world(&local))
+            }
+            Projection::CubeFace(face) => {
+                let local = match face {
+                    CubeFace::Front => Vec3::new(a, b, -1.0),
+                    CubeFace::Back => Vec3::new(-a, b, 1.0),
+                    CubeFace::Right => Vec3::new(1.0, b, a),
+                    CubeFace::Left => Vec3::new(-1.0, b, -a),
+                    CubeFace::Up => Vec3::new(a, 1.0, b),
+                    CubeFace::Down => Vec3::new(a, -1.0, -b),
+                };
+                (self.camera_center, self.to_world(&local))
+            }
+        };
+
+        let mut ray = Ray::new(origin, dir);
+        if self.spectral {
+            ray.wavelengths = Some(Wavelengths::sample(rand::rng().random()));
+        }
+        Some(ray)
+    }
+
+    fn to_world(&self, local: &Vec3) -> Vec3 {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    fn sample_pixel<W: Hittable>(&self, x: usize, y: usize, scene: &Scene<W>) -> Color {
+        match self.get_ray(x, y) {
+            Some(ray) => self.sample_ray(&ray, scene, &mut PathRecord::default()),
+            None => Color::zeros(),
+        }
+    }
+
+    // The color seen along a camera ray, with the rest of what the path saw
+    // written to `record`.
+    fn sample_ray<W: Hittable>(
+        &self,
+        ray: &Ray,
+        scene: &Scene<W>,
+        record: &mut PathRecord,
+    ) -> Color {
+        let color = self.ray_color(
+            ray,
+            scene,
+            self.max_depth,
+            &Color::new(1.0, 1.0, 1.0),
+            None,
+            record,
+        );
+        self.to_rgb(ray, color) * self.exposure
+    }
+
+    fn to_rgb(&self, ray: &Ray, color: Color) -> Color {
+        match &ray.wavelengths {
+            Some(wavelengths) => spectral_to_rgb(&color, wavelengths),
+            None => color,
+        }
+    }
+
+    // All AOVs of a pixel, filled in from the paths traced for its color.
+    // Depth and IDs are those of the first sample so they are not blended
+    // across edges, the rest is averaged over the samples.
+    pub(crate) fn aov_pixel<W: Hittable>(&self, x: usize, y: usize, scene: &Scene<W>) -> AovPixel {
+        let mut pixel = AovPixel {
+            depth: f64::INFINITY,
+            ..AovPixel::default()
+        };
+        for sample in 0..self.samples_per_pixel {
+            let Some(ray) = self.get_ray(x, y) else {
+                continue;
+            };
+            let mut record = PathRecord::default();
+            pixel.color += self.sample_ray(&ray, scene, &mut record);
+            pixel.direct += self.to_rgb(&ray, record.direct) * self.exposure;
+            let Some(hit) = record.first_hit else {
+                continue;
+            };
+            pixel.normal += hit.normal;
+            pixel.albedo += self.to_rgb(&ray, record.albedo);
+            if sample == 0 {
+                pixel.depth = hit.t * ray.direction().norm();
+                pixel.object_id = hit.object_id;
+                pixel.material_id = hit.mat.id(&hit);
+            }
+        }
+        let scale = (self.samples_per_pixel as f64).recip();
+        pixel.color *= scale;
+        pixel.direct *= scale;
+        pixel.indirect = pixel.color - pixel.direct;
+        pixel.normal = pixel.normal.try_normalize(0.0).unwrap_or_else(Vec3::zeros);
+        pixel.albedo *= scale;
+        pixel
+    }
+
+    // `throughput` is the weight of the path up to `ray` and `scatter_pdf`
+    // the density with which the previous bounce picked `ray`, if that bounce
+    // also sampled the environment directly.
+    fn ray_color<W: Hittable>(
+        &self,
+        ray: &Ray,
+        scene: &Scene<W>,
+        depth: usize,
+        throughput: &Color,
+        scatter_pdf: Option<f64>,
+        record: &mut PathRecord,
+    ) -> Color {
+        if depth == 0 {
+            return Color::zeros();
+        }
+        // Scattering in media is followed in a loop with its own step limit,
+        // so that random walks through dense media don't use up the bounces
+        // of the path.
+        let mut ray = *ray;
+        let mut walk_throughput = *throughput;
+        let mut walk_weight = Color::new(1.0, 1.0, 1.0);
+        let mut scatter_pdf = scatter_pdf;
+        let mut steps = 0;
+        let (hit, transmittance) = loop {
+            let hit = hit_opaque(&scene.world, &ray, &(0.001..f64::INFINITY));
+            let distance = hit.as_ref().map_or(f64::INFINITY, |hit| hit.t) * ray.direction().norm();
+            match self.sample_medium(&ray, distance, &walk_throughput) {
+                (weight, Some(scattered)) => {
+                    steps += 1;
+                    if steps > MAX_MEDIUM_STEPS {
+                        return Color::zeros();
+                    }
+                    walk_weight.component_mul_assign(&weight);
+                    walk_throughput.component_mul_assign(&weight);
+                    ray = scattered;
+                    scatter_pdf = None;
+                }
+                (weight, None) => break (hit, walk_weight.component_mul(&weight)),
+            }
+        };
+        let ray = &ray;
+        let bounces = self.max_depth - depth + steps;
+
+        let Some(hit) = hit else {
+            let radiance = spectral(ray, scene.environment.radiance(&ray.direction()))
+                .component_mul(&transmittance);
+            let radiance = match scatter_pdf {
+                Some(scatter_pdf) => {
+                    let light_pdf = scene.environment.pdf(&ray.direction());
+                    radiance * power_heuristic(scatter_pdf, light_pdf)
+                }
+                None => radiance,
+            };
+            if bounces <= 1 {
+                record.direct += throughput.component_mul(&radiance);
+            }
+            return radiance;
+        };
+
+        if bounces == 0 {
+            record.first_hit = Some(hit.clone());
+        }
+        let emitted = spectral(ray, hit.mat.emitted(ray, &hit));
+        if bounces <= 1 {
+            record.direct += throughput
+                .component_mul(&emitted)
+                .component_mul(&transmittance);
+        }
+        let Some((attenuation, mut scattered)) = hit.mat.scatter(ray, &hit) else {
+            return emitted.component_mul(&transmittance);
+        };
+        if hit.mat.medium().is_none() {
+            scattered.media = ray.media;
+            scattered.wavelengths = ray.wavelengths;
+        }
+        let mut attenuation = if hit.mat.is_spectral() {
+            attenuation
+        } else {
+            spectral(ray, attenuation)
+        };
+        if bounces == 0 {
+            record.albedo = attenuation;
+        }
+        if let (Some(before), Some(after)) = (ray.wavelengths, scattered.wavelengths)
+            && after.hero_only
+            && !before.hero_only
+        {
+            // The hero wavelength now stands in for all three.
+            attenuation.component_mul_assign(&Color::new(3.0, 0.0, 0.0));
+        }
+
+        let mut color = emitted;
+        let mut next_scatter_pdf = None;
+        if self.light_sampling && hit.mat.eval(ray, &hit, &scattered.direction()).is_some() {
+            let light = sample_environment(ray, &hit, scene);
+            if bounces == 0 {
+                record.direct += throughput
+                    .component_mul(&light)
+                    .component_mul(&transmittance);
+            }
+            color += light;
+            next_scatter_pdf = Some(hit.mat.pdf(ray, &hit, &scattered.direction()));
+        }
+
+        let next_throughput = throughput
+            .component_mul(&transmittance)
+            .component_mul(&attenuation);
+        (color
+            + attenuation.component_mul(&self.ray_color(
+                &scattered,
+                scene,
+                depth - 1,
+                &next_throughput,
+                next_scatter_pdf,
+                record,
+            )))
+        .component_mul(&transmittance)
+    }
+
+    // Follows the ray through the medium it travels in for up to `distance`.
+    // Returns the weight of the path so far and, if the light scatters in a
+    // participating medium before getting there, the scattered ray.
+    fn sample_medium(&self, ray: &Ray, distance: f64, throughput: &Color) -> (Color, Option<Ray>) {
+        let Some(medium) = ray.media.current() else {
+            return (Color::new(1.0, 1.0, 1.0), None);
+        };
+        let absorption = spectral(ray, medium.absorption());
+        if !medium.is_scattering() {
+            return (beer_lambert(&absorption, distance), None);
+        }
+
+        // Distances are sampled for a channel picked in proportion to the
+        // path throughput, and weighted by the density averaged over all
+        // channels, which keeps long random walks in colored media from
+        // turning into fireflies.
+        let scattering = spectral(ray, medium.scattering());
+        let extinction = absorption + scattering;
+        let total = throughput.sum();
+        let channel_probability = if total > 0.0 {
+            throughput / total
+        } else {
+            Color::repeat(1.0 / 3.0)
+        };
+        let u: f64 = rand::rng().random();
+        let channel = if u < channel_probability.x {
+            0
+        } else if u < channel_probability.x + channel_probability.y {
+            1
+        } else {
+            2
+        };
+        let sampled = if extinction[channel] > 0.0 {
+            -(1.0 - rand::rng().random::<f64>()).ln() / extinction[channel]
         } else {
-            self.camera_center
+            f64::INFINITY
         };
-        let dir = sample_center - origin;
-        Ray::new(origin, dir)
+
+        if sampled < distance {
+            let transmittance = beer_lambert(&extinction, sampled);
+            let pdf = extinction
+                .component_mul(&transmittance)
+                .dot(&channel_probability);
+            let direction = sample_henyey_greenstein(
+                &ray.direction(),
+                medium.anisotropy as f64,
+                rand::rng().random(),
+                rand::rng().random(),
+            );
+            let mut scattered = Ray::new(ray.at(sampled / ray.direction().norm()), direction);
+            scattered.media = ray.media;
+            scattered.wavelengths = ray.wavelengths;
+            let weight = if pdf > 0.0 {
+                scattering.component_mul(&transmittance) / pdf
+            } else {
+                Color::zeros()
+            };
+            (weight, Some(scattered))
+        } else {
+            let transmittance = beer_lambert(&extinction, distance);
+            let pdf = transmittance.dot(&channel_probability);
+            let weight = if pdf > 0.0 {
+                transmittance / pdf
+            } else {
+                Color::zeros()
+            };
+            (weight, None)
+        }
     }
 }
 
-fn ray_color<W: Hittable>(ray: &Ray, world: &W, depth: usize) -> Color {
-    if depth == 0 {
+// Whether a ray arriving at an angle of 45 degrees leaves the surface
+// unchanged, so that glass hit head on still counts as visible.
+fn passes_straight_through(ray: &Ray, hit: &HitRecord) -> bool {
+    let (tangent, _) = orthonormal_basis(&hit.normal);
+    let direction = (tangent - hit.normal).normalize();
+    let probe = Ray {
+        orig: hit.p - direction,
+        dir: direction,
+        ..*ray
+    };
+    hit.mat.scatter(&probe, hit).is_some_and(|(_, scattered)| {
+        scattered.direction().normalize().dot(&direction) > 1.0 - 1e-6
+            && (scattered.origin() - hit.p).norm() < 1e-6 * hit.p.norm().max(1.0)
+    })
+}
+
+fn sample_environment<W: Hittable>(ray: &Ray, hit: &HitRecord, scene: &Scene<W>) -> Color {
+    let Some((direction, light_pdf)) = scene.environment.sample() else {
+        return Color::zeros();
+    };
+    let Some(f) = hit.mat.eval(ray, hit, &direction) else {
+        return Color::zeros();
+    };
+    if light_pdf <= 0.0 || near_zero(&f) {
         return Color::zeros();
     }
 
-    if let Some(hit) = world.hit(ray, &(0.001..f64::INFINITY)) {
-        if let Some((attenuation, scattered)) = hit.mat.scatter(ray, &hit) {
-            attenuation.component_mul(&ray_color(&scattered, world, depth - 1))
-        } else {
-            Color::zeros()
-        }
+    let shadow_ray = Ray::new(hit.p, direction);
+    if hit_opaque(&scene.world, &shadow_ray, &(0.001..f64::INFINITY)).is_some() {
+        return Color::zeros();
+    }
+
+    let scatter_pdf = hit.mat.pdf(ray, hit, &direction);
+    let f = if hit.mat.is_spectral() {
+        f
     } else {
-        let color_a = Color::new(1.0, 1.0, 1.0);
-        let color_b = Color::new(0.5, 0.7, 1.0);
-        let unit_direction = ray.direction().normalize();
-        let tt = 0.5 * (unit_direction.y + 1.0);
-        (1.0 - tt) * color_a + tt * color_b
+        spectral(ray, f)
+    };
+    f.component_mul(&spectral(ray, scene.environment.radiance(&direction)))
+        * power_heuristic(light_pdf, scatter_pdf)
+        / light_pdf
+}
+
+// What a camera path saw besides its color.
+#[derive(Default)]
+struct PathRecord {
+    // Light reaching the camera with at most one bounce
+    direct: Color,
+    // The first surface hit, if the camera ray got there without scattering
+    // in a medium, and the attenuation of the bounce off it
+    first_hit: Option<HitRecord>,
+    albedo: Color,
+}
+
+// Scattering events a path may take in media between two surfaces.
+const MAX_MEDIUM_STEPS: usize = 1024;
+
+// Evaluates an RGB quantity at the wavelengths of the ray in spectral mode.
+fn spectral(ray: &Ray, color: Color) -> Color {
+    match &ray.wavelengths {
+        Some(wavelengths) => rgb_to_spectral(&color, wavelengths),
+        None => color,
     }
 }
+
+// Multiple importance sampling weight for a sample drawn with density `pdf`
+// that could also have been drawn with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
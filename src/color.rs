@@ -1,5 +1,9 @@
 pub type Color = crate::Vec3;
 
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
 fn linear_to_gamma(linear_component: f64) -> f64 {
     // gamma 2
     linear_component.max(0.0).sqrt()
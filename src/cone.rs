@@ -0,0 +1,85 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, azimuth};
+use crate::material::Material;
+use crate::roots::solve_quadratic;
+
+// A cone around the y axis with its base of `radius` at y = 0 and its apex at
+// y = height. Use `Transformed` to place it in the world.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat: Rc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, capped: bool, mat: Rc<dyn Material>) -> Self {
+        Cone {
+            radius: radius.max(0.0),
+            height: height.max(1e-12),
+            capped,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y;
+        let mut t_range = t_range.clone();
+        let mut hit: Option<HitRecord> = None;
+
+        // x^2 + z^2 = k^2 (height - y)^2
+        let roots = solve_quadratic(
+            d.x * d.x + d.z * d.z - k2 * d.y * d.y,
+            2.0 * (o.x * d.x + o.z * d.z + k2 * h * d.y),
+            o.x * o.x + o.z * o.z - k2 * h * h,
+        );
+        for t in roots {
+            let p = ray.at(t);
+            if !t_range.contains(&t) || !(0.0..=self.height).contains(&p.y) {
+                continue;
+            }
+            let gradient = Vec3::new(p.x, k2 * (self.height - p.y), p.z);
+            let outward_normal = if gradient.norm_squared() > 0.0 {
+                gradient.normalize()
+            } else {
+                Vec3::y()
+            };
+            t_range.end = t;
+            hit = Some(
+                HitRecord::new(t, p, self.mat.clone(), outward_normal, ray)
+                    .with_uv(azimuth(&p), p.y / self.height),
+            );
+        }
+
+        if self.capped && d.y.abs() > 1e-12 {
+            let t = -o.y / d.y;
+            let p = ray.at(t);
+            if t_range.contains(&t) && p.x * p.x + p.z * p.z <= self.radius * self.radius {
+                hit = Some(
+                    HitRecord::new(t, p, self.mat.clone(), -Vec3::y(), ray).with_uv(
+                        0.5 * (p.x / self.radius + 1.0),
+                        0.5 * (p.z / self.radius + 1.0),
+                    ),
+                );
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(
+            Point3::new(-self.radius, 0.0, -self.radius),
+            Point3::new(self.radius, self.height, self.radius),
+        )
+    }
+}
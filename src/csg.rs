@@ -0,0 +1,237 @@
+use crate::Ray;
+use crate::Rc;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+
+// Constructive solid geometry over two closed objects. The surface of the
+// result is where the combined inside/outside state changes along the ray,
+// and every hit keeps the material of the child surface it came from.
+
+#[derive(Debug, Clone)]
+struct CsgPair {
+    a: Rc<dyn Hittable>,
+    b: Rc<dyn Hittable>,
+}
+
+impl CsgPair {
+    fn new<A: Hittable + 'static, B: Hittable + 'static>(a: A, b: B) -> Self {
+        CsgPair {
+            a: Rc::new(a),
+            b: Rc::new(b),
+        }
+    }
+
+    // `inside` combines whether the ray is inside a and inside b. With
+    // `invert_b` b is replaced by its complement, as needed for differences.
+    fn hit_all(
+        &self,
+        ray: &Ray,
+        t_range: &std::ops::Range<f64>,
+        inside: fn(bool, bool) -> bool,
+        invert_b: bool,
+    ) -> Vec<HitRecord> {
+        let hits_a = self.a.hit_all(ray, t_range);
+        let mut hits_b = self.b.hit_all(ray, t_range);
+        if invert_b {
+            for hit in &mut hits_b {
+                hit.front_face = !hit.front_face;
+            }
+        }
+
+        // A first crossing leaving an object means the range starts inside it.
+        // Without crossings the ray is outside, which for an inverted b means
+        // inside its complement.
+        let mut in_a = hits_a.first().is_some_and(|hit| !hit.front_face);
+        let mut in_b = hits_b.first().map_or(invert_b, |hit| !hit.front_face);
+
+        let mut events: Vec<(bool, HitRecord)> = hits_a
+            .into_iter()
+            .map(|hit| (true, hit))
+            .chain(hits_b.into_iter().map(|hit| (false, hit)))
+            .collect();
+        events.sort_by(|(_, x), (_, y)| x.t.total_cmp(&y.t));
+
+        let mut hits = Vec::new();
+        for (from_a, hit) in events {
+            let was_inside = inside(in_a, in_b);
+            if from_a {
+                in_a = hit.front_face;
+            } else {
+                in_b = hit.front_face;
+            }
+            if inside(in_a, in_b) != was_inside {
+                hits.push(hit);
+            }
+        }
+        hits
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Union {
+    pair: CsgPair,
+}
+
+impl Union {
+    pub fn new<A: Hittable + 'static, B: Hittable + 'static>(a: A, b: B) -> Self {
+        Union {
+            pair: CsgPair::new(a, b),
+        }
+    }
+}
+
+impl Hittable for Union {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        self.hit_all(ray, t_range).into_iter().next()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.pair
+            .a
+            .bounding_box()
+            .union(&self.pair.b.bounding_box())
+    }
+
+    fn hit_all(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Vec<HitRecord> {
+        self.pair.hit_all(ray, t_range, |a, b| a || b, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Intersection {
+    pair: CsgPair,
+}
+
+impl Intersection {
+    pub fn new<A: Hittable + 'static, B: Hittable + 'static>(a: A, b: B) -> Self {
+        Intersection {
+            pair: CsgPair::new(a, b),
+        }
+    }
+}
+
+impl Hittable for Intersection {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        self.hit_all(ray, t_range).into_iter().next()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.pair
+            .a
+            .bounding_box()
+            .intersection(&self.pair.b.bounding_box())
+    }
+
+    fn hit_all(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Vec<HitRecord> {
+        if self.bounding_box().hit(ray, t_range).is_none() {
+            return Vec::new();
+        }
+        self.pair.hit_all(ray, t_range, |a, b| a && b, false)
+    }
+}
+
+// Everything inside `a` that is not inside `b`.
+#[derive(Debug, Clone)]
+pub struct Difference {
+    pair: CsgPair,
+}
+
+impl Difference {
+    pub fn new<A: Hittable + 'static, B: Hittable + 'static>(a: A, b: B) -> Self {
+        Difference {
+            pair: CsgPair::new(a, b),
+        }
+    }
+}
+
+impl Hittable for Difference {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        self.hit_all(ray, t_range).into_iter().next()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.pair.a.bounding_box()
+    }
+
+    fn hit_all(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Vec<HitRecord> {
+        if self.bounding_box().hit(ray, t_range).is_none() {
+            return Vec::new();
+        }
+        self.pair.hit_all(ray, t_range, |a, b| a && b, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::{Point3, Vec3};
+
+    // Unit spheres around x = -0.5 and x = 0.5, overlapping between -0.5
+    // and 0.5.
+    fn spheres() -> (Sphere, Sphere) {
+        let mat = Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        (
+            Sphere::new(Point3::new(-0.5, 0.0, 0.0), 1.0, mat.clone()),
+            Sphere::new(Point3::new(0.5, 0.0, 0.0), 1.0, mat),
+        )
+    }
+
+    // The t and front_face of every crossing of a ray along +x from `x`.
+    fn crossings(object: &dyn Hittable, x: f64) -> Vec<(f64, bool)> {
+        let ray = Ray::new(Point3::new(x, 0.0, 0.0), Vec3::x());
+        object
+            .hit_all(&ray, &(0.001..f64::INFINITY))
+            .iter()
+            .map(|hit| (hit.t, hit.front_face))
+            .collect()
+    }
+
+    fn assert_crossings(actual: &[(f64, bool)], expected: &[(f64, bool)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} vs {expected:?}");
+        for ((t, front), (expected_t, expected_front)) in actual.iter().zip(expected) {
+            assert!((t - expected_t).abs() < 1e-9, "{actual:?} vs {expected:?}");
+            assert_eq!(front, expected_front, "{actual:?} vs {expected:?}");
+        }
+    }
+
+    #[test]
+    fn union_spans_both() {
+        let (a, b) = spheres();
+        let union = Union::new(a, b);
+        assert_crossings(&crossings(&union, -5.0), &[(3.5, true), (6.5, false)]);
+        // Starting inside, only the exit is left
+        assert_crossings(&crossings(&union, 0.0), &[(1.5, false)]);
+    }
+
+    #[test]
+    fn intersection_is_the_overlap() {
+        let (a, b) = spheres();
+        let intersection = Intersection::new(a, b);
+        assert_crossings(
+            &crossings(&intersection, -5.0),
+            &[(4.5, true), (5.5, false)],
+        );
+    }
+
+    #[test]
+    fn difference_removes_the_overlap() {
+        let (a, b) = spheres();
+        let difference = Difference::new(a, b);
+        // The exit is the surface of b, facing into the removed part
+        assert_crossings(&crossings(&difference, -5.0), &[(3.5, true), (4.5, false)]);
+        assert!(crossings(&difference, 0.0).is_empty());
+    }
+
+    #[test]
+    fn hit_is_the_first_crossing() {
+        let (a, b) = spheres();
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::x());
+        let hit = Intersection::new(a, b)
+            .hit(&ray, &(0.001..f64::INFINITY))
+            .unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert!(hit.front_face);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::quad::Quad;
+
+// An axis-aligned box made of six outward facing quads.
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    sides: [Quad; 6],
+}
+
+impl Cuboid {
+    pub fn new(a: Point3, b: Point3, mat: Rc<dyn Material>) -> Self {
+        let min = a.inf(&b);
+        let max = a.sup(&b);
+
+        let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+        Cuboid {
+            sides: [
+                // front
+                Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, mat.clone()),
+                // right
+                Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, mat.clone()),
+                // back
+                Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, mat.clone()),
+                // left
+                Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, mat.clone()),
+                // top
+                Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, mat.clone()),
+                // bottom
+                Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, mat),
+            ],
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let mut t_range = t_range.clone();
+        let mut hit: Option<HitRecord> = None;
+
+        for side in &self.sides {
+            if let Some(new_hit) = side.hit(ray, &t_range) {
+                t_range.end = new_hit.t;
+                hit = Some(new_hit);
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.sides
+            .iter()
+            .fold(Aabb::empty(), |aabb, side| aabb.union(&side.bounding_box()))
+    }
+}
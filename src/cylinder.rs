@@ -0,0 +1,81 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, azimuth};
+use crate::material::Material;
+use crate::roots::solve_quadratic;
+
+// A cylinder around the y axis centered at the origin, spanning
+// -height / 2 ..= height / 2. Use `Transformed` to place it in the world.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat: Rc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, height: f64, capped: bool, mat: Rc<dyn Material>) -> Self {
+        Cylinder {
+            radius: radius.max(0.0),
+            height: height.max(0.0),
+            capped,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let half = self.height / 2.0;
+        let mut t_range = t_range.clone();
+        let mut hit: Option<HitRecord> = None;
+
+        let roots = solve_quadratic(
+            d.x * d.x + d.z * d.z,
+            2.0 * (o.x * d.x + o.z * d.z),
+            o.x * o.x + o.z * o.z - self.radius * self.radius,
+        );
+        for t in roots {
+            let p = ray.at(t);
+            if !t_range.contains(&t) || p.y.abs() > half {
+                continue;
+            }
+            let outward_normal = Vec3::new(p.x, 0.0, p.z) / self.radius;
+            t_range.end = t;
+            hit = Some(
+                HitRecord::new(t, p, self.mat.clone(), outward_normal, ray)
+                    .with_uv(azimuth(&p), (p.y + half) / self.height),
+            );
+        }
+
+        if self.capped && d.y.abs() > 1e-12 {
+            for y in [half, -half] {
+                let t = (y - o.y) / d.y;
+                let p = ray.at(t);
+                if !t_range.contains(&t) || p.x * p.x + p.z * p.z > self.radius * self.radius {
+                    continue;
+                }
+                let outward_normal = Vec3::new(0.0, y.signum(), 0.0);
+                t_range.end = t;
+                hit = Some(
+                    HitRecord::new(t, p, self.mat.clone(), outward_normal, ray).with_uv(
+                        0.5 * (p.x / self.radius + 1.0),
+                        0.5 * (p.z / self.radius + 1.0),
+                    ),
+                );
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Point3::new(self.radius, self.height / 2.0, self.radius);
+        Aabb::new(-extent, extent)
+    }
+}
@@ -2,6 +2,7 @@ use crate::Point3;
 use crate::Ray;
 use crate::Rc;
 use crate::Vec3;
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 
@@ -42,12 +43,29 @@ impl Hittable for Disk {
             return None;
         }
 
-        Some(HitRecord::new(
-            t,
-            ray.at(t),
-            self.mat.clone(),
-            self.normal,
-            ray,
-        ))
+        // Polar coordinates, u going around the normal and v outwards.
+        let (tangent, bitangent) = crate::orthonormal_basis(&self.normal.normalize());
+        let offset = intersection - self.center;
+        let (x, y) = (offset.dot(&tangent), offset.dot(&bitangent));
+        let u = (y.atan2(x) / (2.0 * std::f64::consts::PI)).rem_euclid(1.0);
+        let radial = if x == 0.0 && y == 0.0 {
+            tangent
+        } else {
+            offset.normalize()
+        };
+        let dpdu = 2.0 * std::f64::consts::PI * self.normal.normalize().cross(&offset);
+        let dpdv = self.radius * radial;
+
+        Some(
+            HitRecord::new(t, intersection, self.mat.clone(), self.normal, ray)
+                .with_uv(u, offset.norm() / self.radius)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let n = self.normal.normalize();
+        let extent = n.map(|c| self.radius * (1.0 - c * c).max(0.0).sqrt());
+        Aabb::new(self.center - extent, self.center + extent).padded(1e-4)
     }
 }
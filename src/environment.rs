@@ -0,0 +1,341 @@
+use std::f64::consts::PI;
+
+use crate::Vec3;
+use crate::color::{Color, luminance};
+use crate::sampling::{Distribution1D, Distribution2D};
+
+// Radiance arriving from infinitely far away, seen by rays that miss every
+// object in the scene.
+#[cfg(feature = "rayon")]
+pub trait Environment: std::fmt::Debug + Send + Sync {
+    fn radiance(&self, direction: &Vec3) -> Color;
+
+    // Picks a unit direction towards the environment for light sampling and
+    // returns it with its solid angle density. Environments that can't be
+    // importance sampled return None.
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+pub trait Environment: std::fmt::Debug {
+    fn radiance(&self, direction: &Vec3) -> Color;
+
+    // Picks a unit direction towards the environment for light sampling and
+    // returns it with its solid angle density. Environments that can't be
+    // importance sampled return None.
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantEnvironment {
+    color: Color,
+}
+
+impl ConstantEnvironment {
+    pub fn new(color: Color) -> Self {
+        ConstantEnvironment { color }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn radiance(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        Some((crate::random_unit_vec3(), 1.0 / (4.0 * PI)))
+    }
+
+    fn pdf(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+// The white to blue sky gradient from the tutorial.
+#[derive(Debug, Clone, Copy)]
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Gradient { bottom, top }
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let unit_direction = direction.normalize();
+        let tt = 0.5 * (unit_direction.y + 1.0);
+        (1.0 - tt) * self.bottom + tt * self.top
+    }
+}
+
+// Maps a direction to equirectangular image coordinates in 0..1, with v = 0
+// looking straight up and u = 0.5 looking towards -z.
+pub fn direction_to_equirect(direction: &Vec3) -> (f64, f64) {
+    let d = direction.normalize();
+    let u = 0.5 + d.x.atan2(-d.z) / (2.0 * PI);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+pub fn equirect_to_direction(u: f64, v: f64) -> Vec3 {
+    let theta = v * PI;
+    let phi = (u - 0.5) * 2.0 * PI;
+    Vec3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+// A latitude-longitude image of the environment, usually loaded from an HDR
+// file.
+#[derive(Debug, Clone)]
+pub struct EquirectangularMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    rotation: nalgebra::Rotation3<f64>,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EquirectangularMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        // Rows near the poles cover less solid angle.
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let v = ((i / width) as f64 + 0.5) / height as f64;
+                luminance(pixel) * (v * PI).sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&weights, width, height);
+        EquirectangularMap {
+            width,
+            height,
+            pixels,
+            rotation: nalgebra::Rotation3::identity(),
+            intensity: 1.0,
+            distribution,
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> image::ImageResult<Self> {
+        let (width, height, pixels) = load_pixels(path)?;
+        Ok(Self::new(width, height, pixels))
+    }
+
+    // Rotates the environment around the y axis.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = nalgebra::Rotation3::from_axis_angle(&Vec3::y_axis(), degrees.to_radians());
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        let x = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Environment for EquirectangularMap {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let (u, v) = direction_to_equirect(&(self.rotation.inverse() * direction));
+        self.lookup(u, v) * self.intensity
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        let ((u, v), pdf) = self.distribution.sample(&mut rand::rng());
+        let sin_theta = (v * PI).sin();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        let direction = self.rotation * equirect_to_direction(u, v);
+        Some((direction, pdf / (2.0 * PI * PI * sin_theta)))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = direction_to_equirect(&(self.rotation.inverse() * direction));
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u.rem_euclid(1.0), v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+// The six faces of a cube map in +x, -x, +y, -y, +z, -z order, following
+// the OpenGL orientation convention.
+#[derive(Debug, Clone)]
+pub struct CubeMap {
+    size: usize,
+    faces: [Vec<Color>; 6],
+    intensity: f64,
+    distribution: Distribution1D,
+}
+
+impl CubeMap {
+    pub fn new(size: usize, faces: [Vec<Color>; 6]) -> Self {
+        for face in &faces {
+            assert_eq!(face.len(), size * size, "cube map faces must be square");
+        }
+        // Texels towards the corners of a face cover less solid angle.
+        let weights = faces
+            .iter()
+            .flat_map(|face| {
+                face.iter().enumerate().map(move |(i, texel)| {
+                    let (a, b) = texel_center(size, i);
+                    luminance(texel) / (1.0 + a * a + b * b).powf(1.5)
+                })
+            })
+            .collect();
+        CubeMap {
+            size,
+            faces,
+            intensity: 1.0,
+            distribution: Distribution1D::new(weights),
+        }
+    }
+
+    // Loads the faces from six square images in +x, -x, +y, -y, +z, -z order.
+    #[cfg(feature = "image")]
+    pub fn load<P: AsRef<std::path::Path>>(paths: [P; 6]) -> image::ImageResult<Self> {
+        let mut size = 0;
+        let mut faces: [Vec<Color>; 6] = Default::default();
+        for (face, path) in faces.iter_mut().zip(paths) {
+            let (width, height, pixels) = load_pixels(path)?;
+            if width != height || (size != 0 && width != size) {
+                return Err(image::ImageError::Parameter(
+                    image::error::ParameterError::from_kind(
+                        image::error::ParameterErrorKind::DimensionMismatch,
+                    ),
+                ));
+            }
+            size = width;
+            *face = pixels;
+        }
+        Ok(Self::new(size, faces))
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn texel_index(&self, a: f64, b: f64) -> usize {
+        let x = (((a + 1.0) / 2.0 * self.size as f64) as usize).min(self.size - 1);
+        let y = (((b + 1.0) / 2.0 * self.size as f64) as usize).min(self.size - 1);
+        y * self.size + x
+    }
+}
+
+fn texel_center(size: usize, index: usize) -> (f64, f64) {
+    let a = ((index % size) as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+    let b = ((index / size) as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+    (a, b)
+}
+
+// Returns the face and the coordinates on it in -1..1, with b growing
+// downwards on the face image.
+pub(crate) fn direction_to_face(d: &Vec3) -> (usize, f64, f64) {
+    let abs = d.abs();
+    if abs.x >= abs.y && abs.x >= abs.z {
+        if d.x > 0.0 {
+            (0, -d.z / abs.x, -d.y / abs.x)
+        } else {
+            (1, d.z / abs.x, -d.y / abs.x)
+        }
+    } else if abs.y >= abs.z {
+        if d.y > 0.0 {
+            (2, d.x / abs.y, d.z / abs.y)
+        } else {
+            (3, d.x / abs.y, -d.z / abs.y)
+        }
+    } else if d.z > 0.0 {
+        (4, d.x / abs.z, -d.y / abs.z)
+    } else {
+        (5, -d.x / abs.z, -d.y / abs.z)
+    }
+}
+
+fn face_to_direction(face: usize, a: f64, b: f64) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -b, -a),
+        1 => Vec3::new(-1.0, -b, a),
+        2 => Vec3::new(a, 1.0, b),
+        3 => Vec3::new(a, -1.0, -b),
+        4 => Vec3::new(a, -b, 1.0),
+        _ => Vec3::new(-a, -b, -1.0),
+    }
+}
+
+impl Environment for CubeMap {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let (face, a, b) = direction_to_face(direction);
+        self.faces[face][self.texel_index(a, b)] * self.intensity
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        use rand::Rng;
+        let (index, _) = self.distribution.sample(&mut rand::rng());
+        let texels = self.size * self.size;
+        let (face, texel) = (index / texels, index % texels);
+        let texel_width = 2.0 / self.size as f64;
+        let (a, b) = texel_center(self.size, texel);
+        let a = a + texel_width * rand::rng().random_range(-0.5..0.5);
+        let b = b + texel_width * rand::rng().random_range(-0.5..0.5);
+        let direction = face_to_direction(face, a, b).normalize();
+        let pdf = self.pdf(&direction);
+        (pdf > 0.0).then_some((direction, pdf))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let (face, a, b) = direction_to_face(direction);
+        let index = face * self.size * self.size + self.texel_index(a, b);
+        let texel_area = (2.0 / self.size as f64).powi(2);
+        self.distribution.probability(index) / texel_area * (1.0 + a * a + b * b).powf(1.5)
+    }
+}
+
+#[cfg(feature = "image")]
+fn load_pixels<P: AsRef<std::path::Path>>(
+    path: P,
+) -> image::ImageResult<(usize, usize, Vec<Color>)> {
+    let image = image::open(path)?.into_rgb32f();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let pixels = image
+        .pixels()
+        .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+        .collect();
+    Ok((width, height, pixels))
+}
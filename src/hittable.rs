@@ -1,8 +1,11 @@
 use crate::Rc;
+use rand::random;
 use std::ops::Range;
 
+use crate::Point3;
 use crate::Ray;
 use crate::Vec3;
+use crate::aabb::Aabb;
 use crate::material::Material;
 
 #[derive(Debug, Clone)]
@@ -12,6 +15,18 @@ pub struct HitRecord {
     pub mat: Rc<dyn Material>,
     pub normal: Vec3,
     pub front_face: bool,
+    pub u: f64,
+    pub v: f64,
+    // Derivatives of the position along u and v, zero when the shape has no
+    // surface parameterization
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // 1 based index of the object in the outermost `HittableList`, 0 outside
+    // of lists
+    pub object_id: usize,
+    // Uniform random number drawn once per hit, so stochastic choices of the
+    // material agree between `is_transparent` and `scatter`
+    pub sample: f64,
 }
 
 impl HitRecord {
@@ -28,16 +43,98 @@ impl HitRecord {
             mat,
             normal,
             front_face,
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::zeros(),
+            dpdv: Vec3::zeros(),
+            object_id: 0,
+            sample: 0.5,
+        }
+    }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    // Position derivatives to build a tangent frame from, falling back to an
+    // arbitrary orthonormal basis around the normal when they're degenerate.
+    pub fn tangents(&self) -> (Vec3, Vec3) {
+        if self.dpdu.cross(&self.dpdv).norm_squared() > 1e-16 {
+            (self.dpdu, self.dpdv)
+        } else {
+            crate::orthonormal_basis(&self.normal)
         }
     }
 }
 
+// Angle around the y axis of `p` mapped to 0..1, used as the u coordinate of
+// shapes of revolution.
+pub fn azimuth(p: &Point3) -> f64 {
+    ((-p.z).atan2(p.x) + std::f64::consts::PI) / (2.0 * std::f64::consts::PI)
+}
+
 #[cfg(feature = "rayon")]
 pub trait Hittable: std::fmt::Debug + Send + Sync {
     fn hit(&self, ray: &Ray, t_range: &Range<f64>) -> Option<HitRecord>;
+    fn bounding_box(&self) -> Aabb;
+
+    // Every surface crossing in `t_range` ordered by t. For closed objects the
+    // crossings alternate between entering (front_face) and leaving.
+    fn hit_all(&self, ray: &Ray, t_range: &Range<f64>) -> Vec<HitRecord> {
+        hit_all_by_stepping(self, ray, t_range)
+    }
 }
 
 #[cfg(not(feature = "rayon"))]
 pub trait Hittable: std::fmt::Debug {
     fn hit(&self, ray: &Ray, t_range: &Range<f64>) -> Option<HitRecord>;
+    fn bounding_box(&self) -> Aabb;
+
+    // Every surface crossing in `t_range` ordered by t. For closed objects the
+    // crossings alternate between entering (front_face) and leaving.
+    fn hit_all(&self, ray: &Ray, t_range: &Range<f64>) -> Vec<HitRecord> {
+        hit_all_by_stepping(self, ray, t_range)
+    }
+}
+
+// The closest hit in `t_range` whose material doesn't cut the surface out
+// there, looking further along the ray past transparent hits. Rays are traced
+// through the world with this, so alpha masks work inside every kind of
+// aggregate.
+pub fn hit_opaque<H: Hittable + ?Sized>(
+    object: &H,
+    ray: &Ray,
+    t_range: &Range<f64>,
+) -> Option<HitRecord> {
+    let mut start = t_range.start;
+    loop {
+        let mut hit = object.hit(ray, &(start..t_range.end))?;
+        hit.sample = random();
+        if !hit.mat.is_transparent(&hit) {
+            return Some(hit);
+        }
+        start = hit.t + 1e-9 * hit.t.abs().max(1.0);
+    }
+}
+
+pub fn hit_all_by_stepping<H: Hittable + ?Sized>(
+    object: &H,
+    ray: &Ray,
+    t_range: &Range<f64>,
+) -> Vec<HitRecord> {
+    let mut t_range = t_range.clone();
+    let mut hits = Vec::new();
+    while let Some(hit) = object.hit(ray, &t_range) {
+        t_range.start = hit.t + 1e-7 * hit.t.abs().max(1.0);
+        hits.push(hit);
+    }
+    hits
 }
@@ -1,10 +1,12 @@
 use crate::Rc;
+use crate::aabb::Aabb;
 
 use crate::hittable::{HitRecord, Hittable};
 
 #[derive(Debug)]
 pub struct HittableList {
     objects: Vec<Rc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl Default for HittableList {
@@ -15,21 +17,27 @@ impl Default for HittableList {
 
 impl HittableList {
     pub fn from_vec(objects: Vec<Rc<dyn Hittable>>) -> Self {
-        HittableList { objects }
+        let bbox = objects.iter().fold(Aabb::empty(), |aabb, object| {
+            aabb.union(&object.bounding_box())
+        });
+        HittableList { objects, bbox }
     }
 
     pub fn new() -> Self {
         HittableList {
             objects: Vec::new(),
+            bbox: Aabb::empty(),
         }
     }
 
     pub fn push<T: Hittable + 'static>(&mut self, object: T) {
+        self.bbox = self.bbox.union(&object.bounding_box());
         self.objects.push(Rc::new(object));
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::empty();
     }
 }
 
@@ -38,13 +46,18 @@ impl Hittable for HittableList {
         let mut t_range = t_range.clone();
         let mut hit: Option<HitRecord> = None;
 
-        for object in &self.objects {
-            if let Some(new_hit) = object.hit(ray, &t_range) {
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(mut new_hit) = object.hit(ray, &t_range) {
                 t_range.end = new_hit.t;
+                new_hit.object_id = index + 1;
                 hit = Some(new_hit);
             }
         }
 
         hit
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
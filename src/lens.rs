@@ -0,0 +1,553 @@
+use crate::{Point3, Rc, Vec3, random_vec3_in_unit_disk, texture::Texture};
+use log::warn;
+use rand::Rng;
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone)]
+pub enum ApertureShape {
+    Circular,
+    // A regular polygon formed by `blades` straight blades, rotated by
+    // `rotation` degrees
+    Polygon { blades: usize, rotation: f64 },
+    // Transmission over the square around the aperture, read from the
+    // average of the channels. Black blocks all light.
+    Mask(Rc<dyn Texture>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Aperture {
+    pub shape: ApertureShape,
+    // Strength of the mechanical vignetting that clips the aperture into a
+    // cat's eye towards the image corners, 0 disables it
+    pub cat_eye: f64,
+}
+
+impl Default for Aperture {
+    fn default() -> Self {
+        Aperture {
+            shape: ApertureShape::Circular,
+            cat_eye: 0.0,
+        }
+    }
+}
+
+impl Aperture {
+    // Samples a point on the aperture in unit disk coordinates for the image
+    // position `image_pos`, which has length 1 in the corners. Returns None
+    // when the sample is vignetted.
+    pub fn sample(&self, image_pos: (f64, f64)) -> Option<(f64, f64)> {
+        let (x, y) = match self.shape {
+            ApertureShape::Circular => {
+                let p = random_vec3_in_unit_disk();
+                (p.x, p.y)
+            }
+            ApertureShape::Polygon { blades, rotation } => {
+                // A uniform point in one of the equally sized triangles
+                // around the center
+                let blades = blades.max(3);
+                let k = rand::rng().random_range(0..blades) as f64;
+                let angle = |i: f64| rotation.to_radians() + 2.0 * PI * i / blades as f64;
+                let (a0, a1) = (angle(k), angle(k + 1.0));
+                let (u1, u2): (f64, f64) = (rand::rng().random(), rand::rng().random());
+                let (s, t) = (u1.sqrt() * (1.0 - u2), u1.sqrt() * u2);
+                (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+            }
+            ApertureShape::Mask(_) => {
+                // Rejection sampling so the mask only shapes the bokeh and
+                // does not darken the image
+                (0..64).find_map(|_| {
+                    let p = random_vec3_in_unit_disk();
+                    self.transmits(p.x, p.y).then_some((p.x, p.y))
+                })?
+            }
+        };
+        self.unvignetted(x, y, image_pos).then_some((x, y))
+    }
+
+    // Whether light passes the aperture at a point in unit disk coordinates.
+    // Masks pass light stochastically by their transmission.
+    pub fn transmits(&self, x: f64, y: f64) -> bool {
+        match &self.shape {
+            ApertureShape::Circular => x * x + y * y <= 1.0,
+            ApertureShape::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3) as f64;
+                let apothem = (PI / blades).cos();
+                (0..blades as usize).all(|i| {
+                    let angle = rotation.to_radians() + 2.0 * PI * (i as f64 + 0.5) / blades;
+                    x * angle.cos() + y * angle.sin() <= apothem
+                })
+            }
+            ApertureShape::Mask(mask) => {
+                let (u, v) = ((x + 1.0) / 2.0, (y + 1.0) / 2.0);
+                if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+                    return false;
+                }
+                let value = mask.value(u, v, &Point3::zeros());
+                rand::rng().random::<f64>() < value.sum() / 3.0
+            }
+        }
+    }
+
+    // The cat's eye is the overlap with a second disk shifted towards the
+    // image corner, like the clear opening of the lens barrel seen off axis.
+    pub fn unvignetted(&self, x: f64, y: f64, image_pos: (f64, f64)) -> bool {
+        let (dx, dy) = (
+            x - self.cat_eye * image_pos.0,
+            y - self.cat_eye * image_pos.1,
+        );
+        self.cat_eye == 0.0 || dx * dx + dy * dy <= 1.0
+    }
+}
+
+// Height of a full frame sensor in mm
+pub const SENSOR_HEIGHT: f64 = 24.0;
+
+// Aperture and zoom given like on a full frame camera, taking world units as
+// meters.
+#[derive(Debug, Clone, Copy)]
+pub struct FStop {
+    pub f_number: f64,
+    // In mm
+    pub focal_length: f64,
+}
+
+impl FStop {
+    pub fn new(f_number: f64, focal_length: f64) -> Self {
+        FStop {
+            f_number,
+            focal_length,
+        }
+    }
+
+    // Vertical field of view in degrees
+    pub fn vfov(&self) -> f64 {
+        2.0 * (SENSOR_HEIGHT / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    // Angle in degrees under which the entrance pupil is seen from the
+    // focus plane
+    pub fn defocus_angle(&self, focus_dist: f64) -> f64 {
+        let pupil_radius = self.focal_length / self.f_number / 2.0 * 0.001;
+        2.0 * (pupil_radius / focus_dist).atan().to_degrees()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LensElement {
+    // Signed radius of curvature in mm, positive for surfaces bulging
+    // towards the scene and 0 for the flat aperture stop
+    pub radius: f64,
+    // Distance to the next surface towards the sensor in mm
+    pub thickness: f64,
+    // Refraction index behind the surface, 0 for the aperture stop
+    pub ior: f64,
+    // Diameter of the clear opening in mm
+    pub aperture: f64,
+}
+
+impl LensElement {
+    pub fn new(radius: f64, thickness: f64, ior: f64, aperture: f64) -> Self {
+        LensElement {
+            radius,
+            thickness,
+            ior,
+            aperture,
+        }
+    }
+
+    fn is_stop(&self) -> bool {
+        self.radius == 0.0
+    }
+}
+
+// A thick lens traced surface by surface from a lens prescription, listed
+// from the scene towards the sensor. Lens space has the sensor centered at
+// the origin and the scene towards -z, the same as the camera frame.
+#[derive(Debug, Clone)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+    // Diameter of the aperture stop in mm, at most its prescribed opening
+    pub stop_diameter: f64,
+    // Height of the sensor in mm, the width follows from the aspect ratio
+    pub sensor_height: f64,
+    // World units per mm
+    pub scale: f64,
+}
+
+impl LensSystem {
+    // The thickness of the last element is the distance to the sensor and is
+    // set when the lens is focused.
+    pub fn new(elements: Vec<LensElement>) -> Self {
+        assert!(
+            !elements.is_empty(),
+            "a lens system needs at least one element"
+        );
+        let stop_diameter = elements
+            .iter()
+            .find(|e| e.is_stop())
+            .map_or(f64::INFINITY, |e| e.aperture);
+        LensSystem {
+            elements,
+            stop_diameter,
+            sensor_height: SENSOR_HEIGHT,
+            scale: 0.001,
+        }
+    }
+
+    // A 50mm f/2 double Gauss lens (Tronnier, US patent 2,673,491)
+    pub fn double_gauss_50mm() -> Self {
+        Self::new(vec![
+            LensElement::new(29.475, 3.76, 1.67, 25.2),
+            LensElement::new(84.83, 0.12, 1.0, 25.2),
+            LensElement::new(19.275, 4.025, 1.67, 23.0),
+            LensElement::new(40.77, 3.275, 1.699, 23.0),
+            LensElement::new(12.75, 5.705, 1.0, 18.0),
+            LensElement::new(0.0, 4.5, 0.0, 17.1),
+            LensElement::new(-14.495, 1.18, 1.603, 17.0),
+            LensElement::new(40.77, 6.065, 1.658, 20.0),
+            LensElement::new(-20.385, 0.19, 1.0, 20.0),
+            LensElement::new(437.065, 3.22, 1.717, 20.0),
+            LensElement::new(-39.73, 36.0, 1.0, 20.0),
+        ])
+    }
+
+    pub fn with_stop_diameter(mut self, stop_diameter: f64) -> Self {
+        self.stop_diameter = stop_diameter;
+        self
+    }
+
+    // Opens or closes the stop so that the entrance pupil, the stop as seen
+    // through the front elements, gives the f-number.
+    pub fn with_f_number(mut self, f_number: f64) -> Self {
+        let Some(stop) = self.elements.iter().position(|e| e.is_stop()) else {
+            return self;
+        };
+        // A paraxial ray from infinity reaches the stop scaled by the pupil
+        // magnification
+        let h = self.elements[0].aperture * 1e-3;
+        let origin = Point3::new(h, 0.0, -1e6);
+        if let Some((o, d)) = self.trace_surfaces(origin, Vec3::z(), 0..stop, false, None) {
+            let z = self.surface_positions()[stop];
+            let stop_height = o.x + (z - o.z) / d.z * d.x;
+            let stop_diameter = self.focal_length() / f_number * stop_height / h;
+            let max_diameter = self.elements[stop].aperture;
+            if stop_diameter > max_diameter {
+                warn!("The lens does not open up to f/{f_number}, using its widest aperture");
+            }
+            self.stop_diameter = stop_diameter.min(max_diameter);
+        }
+        self
+    }
+
+    pub fn with_sensor_height(mut self, sensor_height: f64) -> Self {
+        self.sensor_height = sensor_height;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn elements(&self) -> &[LensElement] {
+        &self.elements
+    }
+
+    // Effective focal length in mm from a paraxial ray parallel to the axis.
+    pub fn focal_length(&self) -> f64 {
+        let h = self.elements[0].aperture * 1e-3;
+        let origin = Point3::new(h, 0.0, -1e6);
+        self.trace(origin, Vec3::z(), false, None)
+            .map_or(f64::NAN, |(_, dir)| -h * dir.z / dir.x)
+    }
+
+    // Vertical field of view in degrees when focused at infinity
+    pub fn vfov(&self) -> f64 {
+        2.0 * (self.sensor_height / (2.0 * self.focal_length()))
+            .atan()
+            .to_degrees()
+    }
+
+    // Moves the sensor so that points `distance` mm in front of it are sharp.
+    pub fn focus(&mut self, distance: f64) {
+        let h = self.elements[0].aperture * 1e-3;
+        for _ in 0..16 {
+            let origin = Point3::new(0.0, 0.0, -distance);
+            let target = Point3::new(h, 0.0, self.surface_positions()[0]);
+            let Some((o, d)) = self.trace(origin, target - origin, false, None) else {
+                return;
+            };
+            // Where the ray crosses the axis behind the lens
+            let image_z = o.z - o.x / d.x * d.z;
+            if !image_z.is_finite() {
+                return;
+            }
+            let last = self.elements.len() - 1;
+            self.elements[last].thickness = (self.elements[last].thickness + image_z).max(0.0);
+        }
+    }
+
+    // Fraction of rays from the sensor center towards the rear element which
+    // make it through the lens.
+    pub fn center_transmission(&self, aperture: &Aperture) -> f64 {
+        let n = 64;
+        let rear = self.elements[self.elements.len() - 1];
+        let z = self.surface_positions()[self.elements.len() - 1];
+        let (mut total, mut passed) = (0, 0);
+        for i in 0..n {
+            for j in 0..n {
+                let x = 2.0 * (i as f64 + 0.5) / n as f64 - 1.0;
+                let y = 2.0 * (j as f64 + 0.5) / n as f64 - 1.0;
+                if x * x + y * y > 1.0 {
+                    continue;
+                }
+                total += 1;
+                let target = Point3::new(x, y, 0.0) * rear.aperture / 2.0 + Vec3::z() * z;
+                if self
+                    .trace(Point3::zeros(), target, true, Some(aperture))
+                    .is_some()
+                {
+                    passed += 1;
+                }
+            }
+        }
+        passed as f64 / total as f64
+    }
+
+    // A ray from a point on the sensor towards a random point on the rear
+    // element, traced out into the scene. Returns origin and direction in
+    // lens space, or None if the lens blocks it.
+    pub fn sample_ray(&self, sensor_pos: Point3, aperture: &Aperture) -> Option<(Point3, Vec3)> {
+        let last = self.elements.len() - 1;
+        let p = random_vec3_in_unit_disk() * self.elements[last].aperture / 2.0;
+        let target = p + Vec3::z() * self.surface_positions()[last];
+        self.trace(sensor_pos, target - sensor_pos, true, Some(aperture))
+    }
+
+    // z of each surface vertex
+    fn surface_positions(&self) -> Vec<f64> {
+        let mut z = 0.0;
+        let mut positions: Vec<f64> = self
+            .elements
+            .iter()
+            .rev()
+            .map(|e| {
+                z -= e.thickness;
+                z
+            })
+            .collect();
+        positions.reverse();
+        positions
+    }
+
+    // Refraction index between surface `i` and the next one towards the
+    // sensor.
+    fn ior_behind(&self, i: usize) -> f64 {
+        if self.elements[i].is_stop() {
+            1.0
+        } else {
+            self.elements[i].ior
+        }
+    }
+
+    fn trace(
+        &self,
+        origin: Point3,
+        dir: Vec3,
+        from_sensor: bool,
+        aperture: Option<&Aperture>,
+    ) -> Option<(Point3, Vec3)> {
+        if from_sensor {
+            let surfaces = (0..self.elements.len()).rev();
+            self.trace_surfaces(origin, dir, surfaces, true, aperture)
+        } else {
+            let surfaces = 0..self.elements.len();
+            self.trace_surfaces(origin, dir, surfaces, false, aperture)
+        }
+    }
+
+    fn trace_surfaces(
+        &self,
+        mut origin: Point3,
+        dir: Vec3,
+        surfaces: impl Iterator<Item = usize>,
+        from_sensor: bool,
+        aperture: Option<&Aperture>,
+    ) -> Option<(Point3, Vec3)> {
+        let positions = self.surface_positions();
+        let mut dir = dir.normalize();
+        for i in surfaces {
+            let element = &self.elements[i];
+            let z = positions[i];
+            let (p, normal) = if element.is_stop() {
+                let t = (z - origin.z) / dir.z;
+                if t <= 0.0 {
+                    return None;
+                }
+                (origin + t * dir, Vec3::z())
+            } else {
+                intersect_surface(element.radius, z, &origin, &dir)?
+            };
+
+            let radius = if element.is_stop() {
+                self.stop_diameter.min(element.aperture) / 2.0
+            } else {
+                element.aperture / 2.0
+            };
+            if p.x * p.x + p.y * p.y > radius * radius {
+                return None;
+            }
+            origin = p;
+            if element.is_stop() {
+                if let Some(aperture) = aperture
+                    && !aperture.transmits(p.x / radius, p.y / radius)
+                {
+                    return None;
+                }
+                continue;
+            }
+
+            let (behind, in_front) = (
+                self.ior_behind(i),
+                if i == 0 { 1.0 } else { self.ior_behind(i - 1) },
+            );
+            let eta = if from_sensor {
+                behind / in_front
+            } else {
+                in_front / behind
+            };
+            dir = refract_surface(&dir, &normal, eta)?;
+        }
+        Some((origin, dir))
+    }
+}
+
+// Hit point and normal against the direction of a spherical surface with the
+// vertex at `z` on the axis.
+fn intersect_surface(radius: f64, z: f64, origin: &Point3, dir: &Vec3) -> Option<(Point3, Vec3)> {
+    let center = Point3::new(0.0, 0.0, z + radius);
+    let oc = origin - center;
+    let b = oc.dot(dir);
+    let c = oc.norm_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let (t0, t1) = (-b - discriminant.sqrt(), -b + discriminant.sqrt());
+    // The vertex side of the sphere
+    let t = if (dir.z > 0.0) != (radius < 0.0) {
+        t0
+    } else {
+        t1
+    };
+    if t <= 0.0 {
+        return None;
+    }
+    let p = origin + t * dir;
+    let normal = (p - center).normalize();
+    let normal = if normal.dot(dir) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    Some((p, normal))
+}
+
+fn refract_surface(dir: &Vec3, normal: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta = -dir.dot(normal);
+    let sin2_theta_t = eta * eta * (1.0 - cos_theta * cos_theta);
+    if sin2_theta_t > 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(eta * dir + (eta * cos_theta - cos_theta_t) * normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_gauss_is_a_50mm_lens() {
+        let focal_length = LensSystem::double_gauss_50mm().focal_length();
+        assert!((focal_length - 50.0).abs() < 1.0, "{focal_length}");
+    }
+
+    #[test]
+    fn focused_rays_meet_at_the_focus_distance() {
+        let mut lens = LensSystem::double_gauss_50mm().with_f_number(8.0);
+        let distance = 2000.0;
+        lens.focus(distance);
+        let aperture = Aperture::default();
+        // Few rays from the sensor center get through at f/8, so this takes
+        // enough samples for a run without crossings to be practically
+        // impossible.
+        let mut crossings = 0;
+        for _ in 0..20_000 {
+            let Some((origin, dir)) = lens.sample_ray(Point3::zeros(), &aperture) else {
+                continue;
+            };
+            if dir.x.abs() < 1e-3 {
+                continue;
+            }
+            // Where the ray crosses the optical axis in front of the lens
+            let z = origin.z - origin.x / dir.x * dir.z;
+            assert!((z + distance).abs() < 0.02 * distance, "crosses at {z}");
+            crossings += 1;
+        }
+        assert!(crossings > 10, "{crossings}");
+    }
+
+    #[test]
+    fn f_number_sets_the_entrance_pupil() {
+        let lens = LensSystem::double_gauss_50mm();
+        let wide = lens.clone().with_f_number(4.0).stop_diameter;
+        let narrow = lens.with_f_number(8.0).stop_diameter;
+        assert!((wide / narrow - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn center_transmission_drops_with_the_stop() {
+        let lens = LensSystem::double_gauss_50mm();
+        let aperture = Aperture::default();
+        let open = lens
+            .clone()
+            .with_f_number(2.0)
+            .center_transmission(&aperture);
+        let closed = lens.with_f_number(8.0).center_transmission(&aperture);
+        assert!(open <= 1.0 && closed > 0.0 && closed < open);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one element")]
+    fn empty_lens_systems_are_rejected() {
+        LensSystem::new(Vec::new());
+    }
+
+    #[test]
+    fn polygon_samples_pass_the_polygon() {
+        let aperture = Aperture {
+            shape: ApertureShape::Polygon {
+                blades: 6,
+                rotation: 15.0,
+            },
+            cat_eye: 0.0,
+        };
+        for _ in 0..1000 {
+            let (x, y) = aperture.sample((0.0, 0.0)).unwrap();
+            assert!(aperture.transmits(x, y));
+        }
+        assert!(!aperture.transmits(1.01, 0.0));
+    }
+
+    #[test]
+    fn fstop_matches_a_full_frame_camera() {
+        let normal = FStop::new(2.0, 50.0);
+        assert!((normal.vfov() - 26.99).abs() < 0.01);
+        // A 25mm wide pupil seen from 10m
+        let expected = 2.0 * (0.0125_f64 / 10.0).atan().to_degrees();
+        assert!((normal.defocus_angle(10.0) - expected).abs() < 1e-12);
+    }
+}
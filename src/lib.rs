@@ -58,6 +58,17 @@ pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     v - 2.0 * v.dot(n) * n
 }
 
+// Two unit vectors perpendicular to the unit vector `n` and to each other.
+pub fn orthonormal_basis(n: &Vec3) -> (Vec3, Vec3) {
+    let helper = if n.x.abs() > 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let s = n.cross(&helper).normalize();
+    (s, n.cross(&s))
+}
+
 pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
     let cos_theta = (-uv).dot(n).min(1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * n);
@@ -65,14 +76,49 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
     r_out_perp + r_out_parallel
 }
 
+pub mod aabb;
+pub mod animation;
+pub mod aov;
+pub mod blackhole;
+pub mod bump;
 pub mod camera;
 pub mod color;
+pub mod cone;
+pub mod csg;
 pub mod ray;
+pub mod roots;
+pub mod sampling;
+pub mod scene;
+pub mod sdf;
+pub mod sheet;
+pub mod sky;
 pub use ray::Ray;
+pub mod cuboid;
+pub mod cylinder;
 pub mod disk;
+pub mod environment;
 pub mod hittable;
 pub mod hittable_list;
+pub mod lens;
 pub mod material;
+pub mod medium;
+pub mod microfacet;
+pub mod mix;
+pub mod noise;
+pub mod paraboloid;
+pub mod plane;
+pub mod portal;
+pub mod principled;
+pub mod quad;
+pub mod spectrum;
 pub mod sphere;
+pub mod starfield;
+pub mod stereo;
+pub mod subsurface;
+pub mod texture;
+pub mod thin_film;
+pub mod torus;
+pub mod transform;
+pub mod video;
 
 pub use std::sync::Arc as Rc;
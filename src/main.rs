@@ -1,17 +1,62 @@
+// `Rc` is always an `Arc`, materials holding textures are only `Send` and
+// `Sync` with the rayon feature.
+#![cfg_attr(not(feature = "rayon"), allow(clippy::arc_with_non_send_sync))]
+
 use icg_final_1::{
     Point3, Rc, Vec3,
-    camera::{Camera, CameraSettings, ImageSettings, QualitySettings},
+    blackhole::{BlackHole, BlackHoleSettings},
+    camera::{Camera, CameraSettings, ImageSettings, Projection, QualitySettings},
     color::Color,
     disk::Disk,
+    environment::Environment,
     hittable_list::HittableList,
+    lens::Aperture,
     material::{Checker, Dielectric, Lambertian, Metal},
+    scene::Scene,
+    sky::PhysicalSky,
     sphere::Sphere,
+    starfield::{NebulaSettings, Starfield, StarfieldSettings},
 };
 use std::env::args;
 
 fn main() {
     use rand::SeedableRng;
     env_logger::init();
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let world = match args().nth(1).as_deref() {
+        Some("world") => create_world(&mut rng),
+        Some("world2") => create_world_2(&mut rng),
+        Some("world3") => create_world_3(&mut rng),
+        Some("mandelbulb") => create_mandelbulb_world(&mut rng),
+        _ => {
+            eprintln!(
+                "Usage: cargo run [world|world2|world3|mandelbulb] [sky|starfield|environment.hdr]"
+            );
+            return;
+        }
+    };
+
+    // An optional physical sky, procedural starfield or equirectangular map
+    // replaces the sky gradient
+    let environment: Option<Rc<dyn Environment>> = match args().nth(2).as_deref() {
+        None => None,
+        Some("sky") => Some(Rc::new(PhysicalSky::from_angles(35.0, 60.0, 3.0))),
+        Some("starfield") => Some(Rc::new(
+            Starfield::new(StarfieldSettings::default()).with_nebula(NebulaSettings::default()),
+        )),
+        #[cfg(feature = "image")]
+        Some(path) => Some(Rc::new(
+            icg_final_1::environment::EquirectangularMap::load(path)
+                .unwrap_or_else(|err| panic!("failed to load environment map {path}: {err}")),
+        )),
+        #[cfg(not(feature = "image"))]
+        Some(_) => {
+            eprintln!("Loading environment maps requires the image feature");
+            return;
+        }
+    };
+
     let image_settings = ImageSettings {
         image_width: 1200,
         aspect_ratio: 16.0 / 9.0,
@@ -19,33 +64,33 @@ fn main() {
     let quality_settings = QualitySettings {
         samples_per_pixel: 500,
         max_depth: 400,
+        light_sampling: environment.is_some(),
+        spectral: false,
     };
     let camera_settings = CameraSettings {
+        projection: Projection::Perspective,
         vfov: 20.0,
         focus_dist: 10.0,
         defocus_angle: 0.6,
         camera_center: Point3::new(15.0, 2.0, 3.0),
         camera_lookat: Point3::new(0.0, 0.0, 0.0),
         camera_vup: Point3::new(0.0, 1.0, 0.0),
+        aperture: Aperture::default(),
+        lens_system: None,
+        f_stop: None,
     };
 
     let camera = Camera::new(image_settings, quality_settings, camera_settings);
-    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
-    let world = match args().nth(1).as_deref() {
-        Some("world") => create_world(&mut rng),
-        Some("world2") => create_world_2(&mut rng),
-        Some("world3") => create_world_3(&mut rng),
-        _ => {
-            eprintln!("Usage: cargo run [world|world2|world3]");
-            return;
-        }
+    let scene = match environment {
+        Some(environment) => Scene::new(world, environment),
+        None => Scene::with_sky(world),
     };
 
     #[cfg(feature = "image")]
-    camera.render_to_imgbuf(&world).save("image.png").unwrap();
+    camera.render_to_imgbuf(&scene).save("image.png").unwrap();
 
     #[cfg(not(feature = "image"))]
-    camera.render(&mut std::io::stdout(), &world).unwrap();
+    camera.render(&mut std::io::stdout(), &scene).unwrap();
 }
 
 fn create_world(rng: &mut impl rand::Rng) -> HittableList {
@@ -126,7 +171,11 @@ fn create_world(rng: &mut impl rand::Rng) -> HittableList {
         Rc::new(portal_right_material),
     ));
 
-    add_blackhole(Point3::new(8.0, 1.0, 0.0), &mut world, 1.0);
+    world.push(BlackHole::new(
+        Point3::new(8.0, 1.0, 0.0),
+        1.0,
+        BlackHoleSettings::default(),
+    ));
 
     world
 }
@@ -134,7 +183,11 @@ fn create_world(rng: &mut impl rand::Rng) -> HittableList {
 fn create_world_2(_rng: &mut impl rand::Rng) -> HittableList {
     let mut world = HittableList::new();
 
-    add_blackhole(Point3::new(0.0, 0.0, 0.0), &mut world, 3.0);
+    world.push(BlackHole::new(
+        Point3::new(0.0, 0.0, 0.0),
+        3.0,
+        BlackHoleSettings::default(),
+    ));
     world.push(Disk::new(
         Point3::new(0.0, 0.0, 0.0),
         Vec3::new(0.0, 1.0, 0.21),
@@ -188,24 +241,77 @@ fn create_world_3(_rng: &mut impl rand::Rng) -> HittableList {
         Rc::new(portal_right_material),
     ));
 
-    add_blackhole(portal_left_pos, &mut world, 1.0);
-    add_blackhole(portal_right_pos, &mut world, 1.0);
+    world.push(BlackHole::new(
+        portal_left_pos,
+        1.0,
+        BlackHoleSettings::default(),
+    ));
+    world.push(BlackHole::new(
+        portal_right_pos,
+        1.0,
+        BlackHoleSettings::default(),
+    ));
 
     world
 }
 
-fn add_blackhole(position: Point3, world: &mut HittableList, scale: f64) {
-    use icg_final_1::material::{Black, BlackHoleLayer};
-    const LAYER_COUNT: usize = 64;
-
-    for layer_index in 0..LAYER_COUNT {
-        let radius = (layer_index as f64 / (LAYER_COUNT as f64 / 4.25)).powf(2.5) + 1.0;
-        world.push(Sphere::new(
-            position,
-            radius / 40.0 * scale,
-            Rc::new(BlackHoleLayer::new(radius, LAYER_COUNT as f64)),
-        ));
-    }
+fn create_mandelbulb_world(_rng: &mut impl rand::Rng) -> HittableList {
+    use icg_final_1::aabb::Aabb;
+    use icg_final_1::plane::Plane;
+    use icg_final_1::sdf::{
+        Mandelbulb, RoundedBox, Scale, SdfObject, SdfSphere, SmoothUnion, Translate, Twist,
+    };
 
-    world.push(Sphere::new(position, 0.01, Rc::new(Black::new())));
+    let mut world = HittableList::new();
+
+    world.push(Plane::new(
+        Point3::new(0.0, -2.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Rc::new(Checker::new()),
+    ));
+
+    world.push(SdfObject::new(
+        Scale {
+            inner: Mandelbulb::default(),
+            factor: 1.5,
+        },
+        Aabb::new(Point3::repeat(-1.8), Point3::repeat(1.8)),
+        Rc::new(Lambertian::new(Color::new(0.8, 0.5, 0.3))),
+    ));
+
+    world.push(SdfObject::new(
+        Translate {
+            inner: SmoothUnion {
+                a: Translate {
+                    inner: SdfSphere { radius: 0.7 },
+                    offset: Vec3::new(0.0, 0.5, 0.0),
+                },
+                b: Translate {
+                    inner: SdfSphere { radius: 0.5 },
+                    offset: Vec3::new(0.0, -0.5, 0.4),
+                },
+                k: 0.4,
+            },
+            offset: Vec3::new(0.0, 0.0, 3.5),
+        },
+        Aabb::new(Point3::new(-1.0, -1.5, 2.0), Point3::new(1.0, 1.5, 5.0)),
+        Rc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 0.05)),
+    ));
+
+    world.push(SdfObject::new(
+        Translate {
+            inner: Twist {
+                inner: RoundedBox {
+                    half_extents: Vec3::new(0.5, 1.2, 0.5),
+                    radius: 0.1,
+                },
+                rate: 1.2,
+            },
+            offset: Vec3::new(0.0, 0.0, -3.5),
+        },
+        Aabb::new(Point3::new(-1.0, -1.5, -4.5), Point3::new(1.0, 1.5, -2.5)),
+        Rc::new(Lambertian::new(Color::new(0.2, 0.4, 0.8))),
+    ));
+
+    world
 }
@@ -1,15 +1,109 @@
+use nalgebra::Complex;
 use rand::random;
 
-use crate::{Ray, color::Color, hittable::HitRecord, near_zero, reflect, refract};
+use crate::{
+    Ray, Vec3,
+    color::Color,
+    hittable::HitRecord,
+    medium::Medium,
+    microfacet::{self, Ggx, fresnel_conductor, fresnel_dielectric, refract_through},
+    near_zero,
+    portal::{Frame, MAX_PORTAL_DEPTH, PortalShape},
+    reflect, refract,
+    spectrum::{D_LINE, Dispersion, Wavelengths, rgb_to_spectrum},
+    thin_film::ThinFilm,
+};
 
 #[cfg(feature = "rayon")]
 pub trait Material: std::fmt::Debug + Send + Sync {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+
+    // The BSDF times the cosine term for light leaving along `direction`,
+    // used for light sampling. Materials that only scatter into a few exact
+    // directions, like mirrors, can't be evaluated and return None.
+    fn eval(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Option<Color> {
+        None
+    }
+
+    // Solid angle density of `scatter` choosing `direction`.
+    fn pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        Color::zeros()
+    }
+
+    // The medium enclosed by surfaces of this material. Materials with a
+    // medium update the media of the scattered ray themselves, every other
+    // material passes on the media of the incoming ray.
+    fn medium(&self) -> Option<&Medium> {
+        None
+    }
+
+    // Whether scatter and eval already return values at the wavelengths of
+    // the ray in spectral mode, instead of RGB colors to be upsampled.
+    fn is_spectral(&self) -> bool {
+        false
+    }
+
+    // Whether the surface is cut out at this hit, which ray traversal then
+    // ignores.
+    fn is_transparent(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+
+    // ID written to the material ID pass at this hit, 0 unless set with
+    // `Tagged`.
+    fn id(&self, _hit_record: &HitRecord) -> usize {
+        0
+    }
 }
 
 #[cfg(not(feature = "rayon"))]
 pub trait Material: std::fmt::Debug {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+
+    // The BSDF times the cosine term for light leaving along `direction`,
+    // used for light sampling. Materials that only scatter into a few exact
+    // directions, like mirrors, can't be evaluated and return None.
+    fn eval(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> Option<Color> {
+        None
+    }
+
+    // Solid angle density of `scatter` choosing `direction`.
+    fn pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        Color::zeros()
+    }
+
+    // The medium enclosed by surfaces of this material. Materials with a
+    // medium update the media of the scattered ray themselves, every other
+    // material passes on the media of the incoming ray.
+    fn medium(&self) -> Option<&Medium> {
+        None
+    }
+
+    // Whether scatter and eval already return values at the wavelengths of
+    // the ray in spectral mode, instead of RGB colors to be upsampled.
+    fn is_spectral(&self) -> bool {
+        false
+    }
+
+    // Whether the surface is cut out at this hit, which ray traversal then
+    // ignores.
+    fn is_transparent(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+
+    // ID written to the material ID pass at this hit, 0 unless set with
+    // `Tagged`.
+    fn id(&self, _hit_record: &HitRecord) -> usize {
+        0
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -32,6 +126,20 @@ impl Material for Lambertian {
         let scattered = Ray::new(hit_record.p, scatter_dir);
         Some((self.albedo, scattered))
     }
+
+    fn eval(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        Some(self.albedo * cosine_pdf(&hit_record.normal, direction))
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        cosine_pdf(&hit_record.normal, direction)
+    }
+}
+
+// Density of the cosine weighted hemisphere around `normal`, which is what
+// offsetting the normal by a random unit vector samples.
+pub(crate) fn cosine_pdf(normal: &Vec3, direction: &Vec3) -> f64 {
+    (normal.dot(&direction.normalize()) / std::f64::consts::PI).max(0.0)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -64,38 +172,152 @@ impl Material for Metal {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Dielectric {
-    refraction_index: f64,
+    medium: Medium,
+    film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Dielectric { refraction_index }
+        Dielectric {
+            medium: Medium::new(refraction_index, Color::zeros(), 0),
+            film: None,
+        }
+    }
+
+    // Light traveling through the interior is absorbed following the
+    // Beer-Lambert law with this coefficient per unit of distance.
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.medium.absorption = absorption;
+        self
+    }
+
+    // Tints the glass so that `color` is left after traveling `distance`
+    // through it.
+    pub fn with_transmittance(self, color: Color, distance: f64) -> Self {
+        self.with_absorption(Medium::absorption_for(color, distance))
+    }
+
+    // Makes the index of refraction depend on the wavelength in spectral
+    // mode. RGB rendering uses the index at the d line.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.medium.refraction_index = dispersion.refraction_index(D_LINE);
+        self.medium.dispersion = Some(dispersion);
+        self
+    }
+
+    // Coats the surface with an iridescent film, like a soap bubble when the
+    // dielectric itself has an index of 1.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    // Fills the interior with a scattering medium, for fog in glass or
+    // subsurface scattering.
+    pub fn with_scattering(mut self, scattering: Color, anisotropy: f64) -> Self {
+        self.medium.scattering = scattering;
+        self.medium.anisotropy = anisotropy;
+        self
+    }
+
+    // Where dielectrics overlap, the one with the highest priority wins.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.medium.priority = priority;
+        self
     }
 }
 
 impl Material for Dielectric {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        let ri = if hit_record.front_face {
-            1.0 / self.refraction_index
+        // Work out the media on both sides of the surface. Boundaries of a
+        // medium inside a higher priority one don't exist optically, the ray
+        // only updates its media and carries on.
+        let wavelength = ray_in.wavelengths.map(|w| w.hero());
+        let mut media = ray_in.media;
+        let mut dispersive = self.medium.is_dispersive();
+        let (outer_index, inner_index) = if hit_record.front_face {
+            let outer_index = media.refraction_index();
+            dispersive |= media.current().is_some_and(|m| m.dispersive);
+            let hidden = media
+                .current()
+                .is_some_and(|m| m.priority > self.medium.priority);
+            media.push(&self.medium, wavelength);
+            if hidden {
+                let passed = Ray { media, ..*ray_in }.with_origin(hit_record.p);
+                return Some((Color::new(1.0, 1.0, 1.0), passed));
+            }
+            (outer_index, self.medium.refraction_index_at(wavelength))
         } else {
-            self.refraction_index
+            let hidden = media.contains(&self.medium) && !media.is_current(&self.medium);
+            media.remove(&self.medium);
+            if hidden {
+                let passed = Ray { media, ..*ray_in }.with_origin(hit_record.p);
+                return Some((Color::new(1.0, 1.0, 1.0), passed));
+            }
+            dispersive |= media.current().is_some_and(|m| m.dispersive);
+            (
+                media.refraction_index(),
+                self.medium.refraction_index_at(wavelength),
+            )
+        };
+        let (incident_index, other_index) = if hit_record.front_face {
+            (outer_index, inner_index)
+        } else {
+            (inner_index, outer_index)
         };
+        let ri = incident_index / other_index;
 
         let unit_direction = ray_in.direction().normalize();
 
         let cos_theta = -unit_direction.dot(&hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction = if cannot_refract || reflectance(cos_theta, ri) > random() {
-            reflect(&unit_direction, &hit_record.normal)
+        let mut scattered = Ray::new(hit_record.p, unit_direction);
+        // Other wavelengths would take a different path, only the hero one
+        // carries on.
+        scattered.wavelengths = ray_in.wavelengths.map(|w| Wavelengths {
+            hero_only: w.hero_only || dispersive,
+            ..w
+        });
+
+        // With a film the reflectance depends on the wavelength, so the
+        // choice between reflection and refraction is weighted.
+        let white = Color::new(1.0, 1.0, 1.0);
+        let (reflect_probability, reflected, refracted) = match &self.film {
+            Some(film) if !cannot_refract => {
+                let f = film.reflectance(
+                    hit_record,
+                    cos_theta,
+                    incident_index,
+                    |_| Complex::new(other_index, 0.0),
+                    ray_in.wavelengths.as_ref(),
+                );
+                let p = f.mean().clamp(1e-3, 1.0 - 1e-3);
+                (p, f / p, (white - f) / (1.0 - p))
+            }
+            _ => (reflectance(cos_theta, ri), white, white),
+        };
+        let attenuation = if cannot_refract || reflect_probability > random() {
+            scattered.dir = reflect(&unit_direction, &hit_record.normal);
+            scattered.media = ray_in.media;
+            reflected
         } else {
-            refract(&unit_direction, &hit_record.normal, ri)
+            scattered.dir = refract(&unit_direction, &hit_record.normal, ri);
+            scattered.media = media;
+            refracted
         };
 
-        let scattered = Ray::new(hit_record.p, direction);
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+        Some((attenuation, scattered))
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        Some(&self.medium)
+    }
+
+    fn is_spectral(&self) -> bool {
+        true
     }
 }
 
@@ -105,12 +327,264 @@ fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
 
+// Rough metal using the GGX microfacet model, with a complex index of
+// refraction eta + i k per color channel.
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: Ggx,
+    film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            distribution: Ggx::from_roughness(roughness),
+            film: None,
+        }
+    }
+
+    // Coats the metal with an iridescent film, like an oil slick or heat
+    // tinted steel.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    fn fresnel(&self, ray_in: &Ray, hit_record: &HitRecord, cos_theta: f64) -> Color {
+        match &self.film {
+            Some(film) => film.reflectance(
+                hit_record,
+                cos_theta,
+                1.0,
+                |lambda| {
+                    Complex::new(
+                        rgb_to_spectrum(&self.eta, lambda),
+                        rgb_to_spectrum(&self.k, lambda),
+                    )
+                },
+                ray_in.wavelengths.as_ref(),
+            ),
+            None => fresnel_conductor(cos_theta, &self.eta, &self.k),
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = microfacet::Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let m = self
+            .distribution
+            .sample_visible_normal(&wo, random(), random());
+        let wi = reflect(&-wo, &m);
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        let fresnel = self.fresnel(ray_in, hit_record, wo.dot(&m));
+        let weight = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        Some((
+            fresnel * weight,
+            Ray::new(hit_record.p, frame.to_world(&wi)),
+        ))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let frame = microfacet::Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Some(Color::zeros());
+        }
+
+        let m = (wo + wi).normalize();
+        let fresnel = self.fresnel(ray_in, hit_record, wo.dot(&m));
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g2(&wo, &wi);
+        Some(fresnel * d * g / (4.0 * wo.z))
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let frame = microfacet::Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+
+        let m = (wo + wi).normalize();
+        self.distribution.visible_normal_pdf(&wo, &m) / (4.0 * wo.dot(&m))
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.film.is_some()
+    }
+}
+
+// Frosted glass using the GGX microfacet model for both reflection and
+// transmission.
+#[derive(Debug, Clone, Copy)]
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: Ggx,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        RoughDielectric {
+            refraction_index,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    // Local directions and the relative index of refraction across the
+    // surface as seen from the incoming ray.
+    fn local(&self, ray_in: &Ray, hit_record: &HitRecord) -> (microfacet::Frame, Vec3, f64) {
+        let frame = microfacet::Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let eta = if hit_record.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        };
+        (frame, wo, eta)
+    }
+
+    // Half vector of a reflection or refraction, facing the side of `wo`.
+    fn half_vector(wo: &Vec3, wi: &Vec3, eta: f64) -> Option<Vec3> {
+        let reflected = wi.z > 0.0;
+        let m = if reflected { wo + wi } else { wo + wi * eta };
+        if m.norm_squared() == 0.0 {
+            return None;
+        }
+        let m = m.normalize();
+        let m = if m.z < 0.0 { -m } else { m };
+        // Discard back facing microfacets
+        if wo.dot(&m) <= 0.0 || wi.dot(&m) * wi.z.signum() <= 0.0 {
+            return None;
+        }
+        Some(m)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (frame, wo, eta) = self.local(ray_in, hit_record);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let m = self
+            .distribution
+            .sample_visible_normal(&wo, random(), random());
+        let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+        let wi = match refract_through(&wo, &m, eta) {
+            Some(refracted) if random::<f64>() >= fresnel => refracted,
+            _ => reflect(&-wo, &m),
+        };
+        // Reflections must stay above and refractions below the surface.
+        if wi.z == 0.0 || (wi.z > 0.0) != (wi.dot(&m) > 0.0) {
+            return None;
+        }
+
+        let weight = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        Some((
+            Color::repeat(weight),
+            Ray::new(hit_record.p, frame.to_world(&wi)),
+        ))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let (frame, wo, eta) = self.local(ray_in, hit_record);
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Some(Color::zeros());
+        }
+        let Some(m) = Self::half_vector(&wo, &wi, eta) else {
+            return Some(Color::zeros());
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g2(&wo, &wi);
+        let value = if wi.z > 0.0 {
+            fresnel * d * g / (4.0 * wo.z)
+        } else {
+            let denom = wo.dot(&m) + eta * wi.dot(&m);
+            (1.0 - fresnel) * d * g * (wi.dot(&m) * wo.dot(&m)).abs() / (wo.z * denom * denom)
+                * eta
+                * eta
+        };
+        Some(Color::repeat(value))
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let (frame, wo, eta) = self.local(ray_in, hit_record);
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+        let Some(m) = Self::half_vector(&wo, &wi, eta) else {
+            return 0.0;
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(&m), eta);
+        let pdf_m = self.distribution.visible_normal_pdf(&wo, &m);
+        if wi.z > 0.0 {
+            fresnel * pdf_m / (4.0 * wo.dot(&m))
+        } else {
+            let denom = wo.dot(&m) + eta * wi.dot(&m);
+            (1.0 - fresnel) * pdf_m * eta * eta * wi.dot(&m).abs() / (denom * denom)
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Portal {
-    radius: f64,
     albedo: Color,
-    portal_position: crate::Point3,
-    target_position: crate::Point3,
+    shape: PortalShape,
+    entry: Frame,
+    transfer: Frame,
 }
 
 impl Portal {
@@ -121,10 +595,14 @@ impl Portal {
         target_position: crate::Point3,
     ) -> Self {
         Portal {
-            radius,
             albedo,
-            portal_position,
-            target_position,
+            shape: PortalShape::Sphere { radius },
+            entry: Frame::translation(portal_position.x, portal_position.y, portal_position.z),
+            transfer: Frame::translation(
+                target_position.x - portal_position.x,
+                target_position.y - portal_position.y,
+                target_position.z - portal_position.z,
+            ),
         }
     }
 
@@ -140,28 +618,67 @@ impl Portal {
             Portal::new(radius, albedo_b, pos_b, pos_a),
         )
     }
+
+    // Links two portals of the same shape placed at `frame_a` and `frame_b`.
+    // Planar portals are turned around their local y axis on the way through
+    // so that a ray entering the front of one leaves through the front of the
+    // other.
+    pub fn new_linked_pair(
+        shape: PortalShape,
+        albedo_a: Color,
+        albedo_b: Color,
+        frame_a: Frame,
+        frame_b: Frame,
+    ) -> (Self, Self) {
+        let flip = if shape.is_planar() {
+            Frame::rotation(crate::Vec3::y() * std::f64::consts::PI)
+        } else {
+            Frame::identity()
+        };
+        (
+            Portal {
+                albedo: albedo_a,
+                shape,
+                entry: frame_a,
+                transfer: frame_b * flip * frame_a.inverse(),
+            },
+            Portal {
+                albedo: albedo_b,
+                shape,
+                entry: frame_b,
+                transfer: frame_a * flip * frame_b.inverse(),
+            },
+        )
+    }
 }
 
 impl Material for Portal {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        // Simulate a portal by after traveling the portal then redirecting the ray to the target position
-        use crate::hittable::Hittable;
-        let sphere = crate::sphere::Sphere::new(
-            self.portal_position,
-            self.radius,
-            crate::Rc::new(Black::new()),
-        );
-        let new_ray = Ray::new(hit_record.p, ray_in.direction());
-        let out_rec = sphere.hit(&new_ray, &(0.001..f64::INFINITY));
-        let out_pos = match out_rec {
-            Some(rec) => rec.p,
-            None => return None, // Ray did not hit the portal
+        if ray_in.portal_depth >= MAX_PORTAL_DEPTH {
+            return None;
+        }
+
+        // Volumetric portals teleport the ray from where it would leave the
+        // portal, planar portals from where it hit.
+        let out_pos = if self.shape.is_planar() {
+            hit_record.p
+        } else {
+            let inverse = self.entry.inverse();
+            let origin = inverse.transform_point(&hit_record.p.into()).coords;
+            let direction = inverse.transform_vector(&ray_in.direction());
+            let (t, _) = self
+                .shape
+                .hit_local(&origin, &direction, &(0.001..f64::INFINITY))?;
+            hit_record.p + t * ray_in.direction()
         };
 
-        let scattered = Ray::new(
-            self.target_position + (out_pos - self.portal_position),
-            ray_in.direction(),
-        );
+        let scattered = Ray {
+            orig: self.transfer.transform_point(&out_pos.into()).coords,
+            dir: self.transfer.transform_vector(&ray_in.direction()),
+            portal_depth: ray_in.portal_depth + 1,
+            media: ray_in.media,
+            wavelengths: ray_in.wavelengths,
+        };
         Some((self.albedo, scattered))
     }
 }
@@ -187,14 +704,53 @@ impl Material for Black {
     }
 }
 
+// Shape of the index of refraction of the black hole layers. The defaults
+// give the lensing of the original hand tuned black hole.
+#[derive(Debug, Clone, Copy)]
+pub struct BlackHoleLayerSettings {
+    // Bias of the layer weight towards grazing angles, in 0..1
+    pub blend: f64,
+    // Past this fraction of grazing the weight falls off to zero
+    pub falloff: f64,
+    pub exponent: f64,
+    pub multiplier: f64,
+    // Density of the layers grows as (radius - horizon_offset)^-density_exponent
+    pub horizon_offset: f64,
+    pub density_exponent: f64,
+    pub density_scale: f64,
+}
+
+impl Default for BlackHoleLayerSettings {
+    fn default() -> Self {
+        BlackHoleLayerSettings {
+            blend: 0.9,
+            falloff: 0.91,
+            exponent: 1.74,
+            multiplier: 22.0,
+            horizon_offset: 1.4,
+            density_exponent: 0.5,
+            density_scale: 2.8,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct BlackHoleLayer {
     pre_mult: f64,
+    settings: BlackHoleLayerSettings,
 }
 
 impl BlackHoleLayer {
     pub fn new(radius: f64, layer_count: f64) -> Self {
-        let pre_mult = (radius - 1.4).max(0.0001).powf(-0.5) / layer_count * 2.8;
+        Self::with_settings(radius, layer_count, BlackHoleLayerSettings::default())
+    }
+
+    pub fn with_settings(radius: f64, layer_count: f64, settings: BlackHoleLayerSettings) -> Self {
+        let pre_mult = (radius - settings.horizon_offset)
+            .max(0.0001)
+            .powf(-settings.density_exponent)
+            / layer_count
+            * settings.density_scale;
         debug_assert!(
             pre_mult.is_finite(),
             "Invalid pre_mult value: {}, from r = {}, layer_count = {}",
@@ -202,16 +758,17 @@ impl BlackHoleLayer {
             radius,
             layer_count
         );
-        BlackHoleLayer { pre_mult }
+        BlackHoleLayer { pre_mult, settings }
     }
 }
 
 impl Material for BlackHoleLayer {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let settings = &self.settings;
         let layer_weight = {
             let wi = ray_in.direction().normalize();
             let f = wi.dot(&hit_record.normal).abs();
-            let blend = 0.9f64;
+            let blend = settings.blend;
 
             let f = if blend != 0.5 {
                 let blend = blend.clamp(0.0, 1.0 - 1e-5);
@@ -227,13 +784,13 @@ impl Material for BlackHoleLayer {
             };
 
             let f = 1.0 - f;
-            if f > 0.91 {
-                1.0 - (f - 0.91) / 0.09
+            if f > settings.falloff {
+                1.0 - (f - settings.falloff) / (1.0 - settings.falloff)
             } else {
                 1.0
             }
         };
-        let ri = (self.pre_mult * layer_weight).powf(1.74) * 22.0 + 1.0;
+        let ri = (self.pre_mult * layer_weight).powf(settings.exponent) * settings.multiplier + 1.0;
         let ri = if hit_record.front_face { 1.0 / ri } else { ri };
 
         let unit_direction = ray_in.direction().normalize();
@@ -271,24 +828,145 @@ impl Checker {
     }
 }
 
-impl Material for Checker {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_dir = hit_record.normal + crate::random_unit_vec3();
-        if near_zero(&scatter_dir) {
-            scatter_dir = hit_record.normal;
-        }
-        let scattered = Ray::new(hit_record.p, scatter_dir);
+impl Checker {
+    fn color(&self, hit_record: &HitRecord) -> Color {
         let scale = 2.73;
         let x = (hit_record.p.x * scale).floor() as i32;
         let y = (hit_record.p.y * scale).floor() as i32;
         let z = (hit_record.p.z * scale).floor() as i32;
         let color = (x + y + z) % 2;
-        let color = if color == 0 {
+        if color == 0 {
             Color::new(0.2, 0.2, 0.2)
         } else {
             Color::new(0.8, 0.8, 0.8)
-        };
+        }
+    }
+}
 
-        Some((color, scattered))
+impl Material for Checker {
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let mut scatter_dir = hit_record.normal + crate::random_unit_vec3();
+        if near_zero(&scatter_dir) {
+            scatter_dir = hit_record.normal;
+        }
+        let scattered = Ray::new(hit_record.p, scatter_dir);
+        Some((self.color(hit_record), scattered))
+    }
+
+    fn eval(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        Some(self.color(hit_record) * cosine_pdf(&hit_record.normal, direction))
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        cosine_pdf(&hit_record.normal, direction)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(not(feature = "rayon"), allow(clippy::arc_with_non_send_sync))]
+mod tests {
+    use super::*;
+    use crate::Rc;
+    use std::f64::consts::PI;
+
+    // A hit on the xy-plane seen from `wo`, from outside or inside.
+    fn hit(mat: Rc<dyn Material>, wo: Vec3, front_face: bool) -> (Ray, HitRecord) {
+        let ray = Ray::new(wo.normalize(), -wo.normalize());
+        let outward = if front_face { Vec3::z() } else { -Vec3::z() };
+        let hit = HitRecord::new(1.0, Vec3::zeros(), mat, outward, &ray);
+        (ray, hit)
+    }
+
+    fn materials() -> Vec<Rc<dyn Material>> {
+        vec![
+            Rc::new(Conductor::gold(0.3)),
+            Rc::new(Conductor::aluminium(0.8)),
+            Rc::new(RoughDielectric::new(1.5, 0.3)),
+        ]
+    }
+
+    // Integral of eval over the sphere of directions, from a grid that is
+    // uniform in the cosine and the azimuth.
+    fn albedo(ray: &Ray, hit: &HitRecord) -> Color {
+        let n = 200;
+        let solid_angle = 4.0 * PI / (n * n) as f64;
+        let mut sum = Color::zeros();
+        for i in 0..n {
+            let cos = 2.0 * (i as f64 + 0.5) / n as f64 - 1.0;
+            let sin = (1.0 - cos * cos).sqrt();
+            for j in 0..n {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                let direction = Vec3::new(sin * phi.cos(), sin * phi.sin(), cos);
+                sum += hit.mat.eval(ray, hit, &direction).unwrap();
+            }
+        }
+        sum * solid_angle
+    }
+
+    // Average weight of scattered samples, the albedo as seen by the path
+    // tracer.
+    fn sampled_albedo(ray: &Ray, hit: &HitRecord, samples: usize) -> Color {
+        let sum: Color = (0..samples)
+            .filter_map(|_| hit.mat.scatter(ray, hit))
+            .map(|(attenuation, _)| attenuation)
+            .sum();
+        sum / samples as f64
+    }
+
+    #[test]
+    fn scatter_weights_match_eval_over_pdf() {
+        for mat in materials() {
+            for front_face in [true, false] {
+                let (ray, hit) = hit(mat.clone(), Vec3::new(0.3, -0.2, 0.8), front_face);
+                for _ in 0..1000 {
+                    let Some((attenuation, scattered)) = mat.scatter(&ray, &hit) else {
+                        continue;
+                    };
+                    let direction = scattered.direction();
+                    let f = mat.eval(&ray, &hit, &direction).unwrap();
+                    let pdf = mat.pdf(&ray, &hit, &direction);
+                    assert!(pdf > 0.0, "{mat:?} sampled a direction with zero pdf");
+                    let expected = f / pdf;
+                    assert!(
+                        (attenuation - expected).norm() < 1e-6 * expected.norm().max(1.0),
+                        "{mat:?}: {attenuation:?} vs {expected:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn eval_integrates_to_the_sampled_albedo() {
+        for mat in materials() {
+            let (ray, hit) = hit(mat, Vec3::new(0.5, 0.0, 0.7), true);
+            let integrated = albedo(&ray, &hit);
+            let sampled = sampled_albedo(&ray, &hit, 50_000);
+            assert!(
+                (integrated - sampled).abs().max() < 0.02,
+                "{:?}: {integrated:?} vs {sampled:?}",
+                hit.mat
+            );
+        }
+    }
+
+    #[test]
+    fn white_furnace() {
+        // A perfect conductor and a glass without absorption lose energy
+        // only to the missing multiple scattering between microfacets.
+        let perfect = Conductor::new(Color::zeros(), Color::repeat(1e4), 0.1);
+        let glass = RoughDielectric::new(1.5, 0.1);
+        let materials: [Rc<dyn Material>; 2] = [Rc::new(perfect), Rc::new(glass)];
+        for mat in materials {
+            for cos in [0.2_f64, 0.6, 1.0] {
+                let wo = Vec3::new((1.0 - cos * cos).sqrt(), 0.0, cos);
+                let (ray, hit) = hit(mat.clone(), wo, true);
+                let albedo = sampled_albedo(&ray, &hit, 20_000);
+                assert!(
+                    albedo.max() <= 1.0 + 1e-9 && albedo.min() > 0.97,
+                    "{mat:?} at cos {cos}: {albedo:?}"
+                );
+            }
+        }
     }
 }
@@ -0,0 +1,211 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::Vec3;
+use crate::color::Color;
+use crate::spectrum::Dispersion;
+
+// Rays keep track of at most this many nested media, entering more of them
+// is ignored.
+pub const MAX_NESTED_MEDIA: usize = 8;
+
+static NEXT_MEDIUM_ID: AtomicU32 = AtomicU32::new(0);
+
+// The interior of a closed dielectric. Where media overlap, the one with the
+// highest priority fills the overlap and the boundaries of the others are
+// ignored, so ice in water is modeled as a water volume overlapping a higher
+// priority ice volume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Medium {
+    id: u32,
+    pub refraction_index: f64,
+    // Beer-Lambert absorption coefficient per unit of distance
+    pub absorption: Color,
+    pub priority: u32,
+    // Scattering coefficient per unit of distance, on top of the absorption
+    pub scattering: Color,
+    // Henyey-Greenstein asymmetry of the scattering, from -1 (backwards) to 1
+    // (forwards)
+    pub anisotropy: f64,
+    // Replaces `refraction_index` in spectral mode
+    pub dispersion: Option<Dispersion>,
+}
+
+impl Medium {
+    pub fn new(refraction_index: f64, absorption: Color, priority: u32) -> Self {
+        Medium {
+            id: NEXT_MEDIUM_ID.fetch_add(1, Ordering::Relaxed),
+            refraction_index,
+            absorption,
+            priority,
+            scattering: Color::zeros(),
+            anisotropy: 0.0,
+            dispersion: None,
+        }
+    }
+
+    pub fn refraction_index_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refraction_index(wavelength),
+            _ => self.refraction_index,
+        }
+    }
+
+    pub fn is_scattering(&self) -> bool {
+        self.scattering.iter().any(|&s| s > 0.0)
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+
+    // Fraction of light left after traveling `distance` through the medium.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        beer_lambert(&self.absorption, distance)
+    }
+
+    // Absorption coefficient that leaves `color` after `distance`.
+    pub fn absorption_for(color: Color, distance: f64) -> Color {
+        color.map(|c| -c.clamp(1e-6, 1.0).ln() / distance)
+    }
+}
+
+// Samples a new direction for light traveling along `direction` that
+// scatters following the Henyey-Greenstein phase function.
+pub fn sample_henyey_greenstein(direction: &Vec3, anisotropy: f64, u1: f64, u2: f64) -> Vec3 {
+    let g = anisotropy.clamp(-0.99, 0.99);
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * u1
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u2;
+    let d = direction.normalize();
+    let (s, t) = crate::orthonormal_basis(&d);
+    d * cos_theta + (s * phi.cos() + t * phi.sin()) * sin_theta
+}
+
+pub fn beer_lambert(absorption: &Color, distance: f64) -> Color {
+    absorption.map(|a| if a > 0.0 { (-a * distance).exp() } else { 1.0 })
+}
+
+// What a ray keeps of a medium it is inside of. Colors are stored in single
+// precision and the refraction index is evaluated at the wavelength of the
+// ray on entering, which keeps rays small.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EnteredMedium {
+    id: u32,
+    pub priority: u32,
+    pub refraction_index: f64,
+    absorption: [f32; 3],
+    scattering: [f32; 3],
+    pub anisotropy: f32,
+    pub dispersive: bool,
+}
+
+impl EnteredMedium {
+    fn new(medium: &Medium, wavelength: Option<f64>) -> Self {
+        let single = |c: &Color| [c.x as f32, c.y as f32, c.z as f32];
+        EnteredMedium {
+            id: medium.id,
+            priority: medium.priority,
+            refraction_index: medium.refraction_index_at(wavelength),
+            absorption: single(&medium.absorption),
+            scattering: single(&medium.scattering),
+            anisotropy: medium.anisotropy as f32,
+            dispersive: medium.is_dispersive(),
+        }
+    }
+
+    pub fn absorption(&self) -> Color {
+        self.absorption.map(f64::from).into()
+    }
+
+    pub fn scattering(&self) -> Color {
+        self.scattering.map(f64::from).into()
+    }
+
+    pub fn is_scattering(&self) -> bool {
+        self.scattering.iter().any(|&s| s > 0.0)
+    }
+}
+
+// The media a ray is inside of, in the order they were entered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MediumStack {
+    media: [EnteredMedium; MAX_NESTED_MEDIA],
+    len: usize,
+}
+
+impl MediumStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entered(&self) -> &[EnteredMedium] {
+        &self.media[..self.len]
+    }
+
+    // The medium filling the space the ray travels through: the one with the
+    // highest priority, the most recently entered one on ties.
+    pub fn current(&self) -> Option<&EnteredMedium> {
+        self.entered()
+            .iter()
+            .rev()
+            .reduce(|best, m| if m.priority > best.priority { m } else { best })
+    }
+
+    pub fn refraction_index(&self) -> f64 {
+        self.current().map_or(1.0, |m| m.refraction_index)
+    }
+
+    pub fn contains(&self, medium: &Medium) -> bool {
+        self.entered().iter().any(|m| m.id == medium.id)
+    }
+
+    // Enters `medium`, seen at the hero `wavelength` in spectral mode.
+    pub fn push(&mut self, medium: &Medium, wavelength: Option<f64>) {
+        if self.len < MAX_NESTED_MEDIA {
+            self.media[self.len] = EnteredMedium::new(medium, wavelength);
+            self.len += 1;
+        }
+    }
+
+    pub fn remove(&mut self, medium: &Medium) {
+        let position = self.entered().iter().rposition(|m| m.id == medium.id);
+        if let Some(index) = position {
+            self.media.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+            self.media[self.len] = EnteredMedium::default();
+        }
+    }
+
+    pub fn is_current(&self, medium: &Medium) -> bool {
+        self.current().is_some_and(|m| m.id == medium.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn henyey_greenstein_mean_cosine_is_the_anisotropy() {
+        let direction = Vec3::new(1.0, 2.0, -0.5);
+        let n = 20_000;
+        for g in [-0.7, 0.0, 0.5, 0.9] {
+            let mean = (0..n)
+                .map(|i| {
+                    let u1 = (i as f64 + 0.5) / n as f64;
+                    let u2 = (i as f64 * 0.618_034).fract();
+                    let scattered = sample_henyey_greenstein(&direction, g, u1, u2);
+                    assert!((scattered.norm() - 1.0).abs() < 1e-9);
+                    scattered.dot(&direction.normalize())
+                })
+                .sum::<f64>()
+                / n as f64;
+            assert!((mean - g).abs() < 1e-3, "g {g}: mean cosine {mean}");
+        }
+    }
+}
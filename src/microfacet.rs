@@ -0,0 +1,212 @@
+use std::f64::consts::PI;
+
+use crate::Vec3;
+use crate::color::Color;
+
+// Local shading frame with the normal along +z.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub s: Vec3,
+    pub t: Vec3,
+    pub n: Vec3,
+}
+
+impl Frame {
+    pub fn from_normal(n: &Vec3) -> Self {
+        let (s, t) = crate::orthonormal_basis(n);
+        Frame { s, t, n: *n }
+    }
+
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(v.dot(&self.s), v.dot(&self.t), v.dot(&self.n))
+    }
+
+    pub fn to_world(&self, v: &Vec3) -> Vec3 {
+        v.x * self.s + v.y * self.t + v.z * self.n
+    }
+}
+
+// Isotropic GGX (Trowbridge-Reitz) microfacet distribution. All directions
+// are in the local frame and point away from the surface.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    // Uses the perceptually linear mapping alpha = roughness^2.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Ggx {
+            alpha: (roughness * roughness).clamp(1e-4, 1.0),
+        }
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn d(&self, m: &Vec3) -> f64 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let cos2 = m.z * m.z;
+        let denom = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    fn lambda(&self, v: &Vec3) -> f64 {
+        let cos2 = v.z * v.z;
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * (-1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+
+    pub fn g1(&self, v: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(v))
+    }
+
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of `sample_visible_normal` returning `m` as seen from `wo`.
+    pub fn visible_normal_pdf(&self, wo: &Vec3, m: &Vec3) -> f64 {
+        if wo.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z.abs()
+    }
+
+    // Samples a microfacet normal visible from `wo` (Heitz 2018).
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: f64, u2: f64) -> Vec3 {
+        let wo = if wo.z < 0.0 { -wo } else { *wo };
+        let vh = Vec3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+
+        let len_sq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::x()
+        };
+        let t2 = vh.cross(&t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of
+// refraction eta + i k, per color channel.
+pub fn fresnel_conductor(cos_i: f64, eta: &Color, k: &Color) -> Color {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    eta.zip_map(k, |eta, k| {
+        let cos2 = cos_i * cos_i;
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    })
+}
+
+// Fresnel reflectance of a dielectric interface, `eta` being the index of
+// refraction on the far side over the one on the near side.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// Refracts `wo` (pointing away from the surface on the side of `m`) through
+// the microfacet `m`, None on total internal reflection.
+pub fn refract_through(wo: &Vec3, m: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = wo.dot(m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Integral of `f` over the hemisphere around z, from a grid that is
+    // uniform in the cosine and the azimuth.
+    fn integrate(f: impl Fn(&Vec3) -> f64) -> f64 {
+        let n = 500;
+        let solid_angle = 2.0 * PI / (n * n) as f64;
+        let mut sum = 0.0;
+        for i in 0..n {
+            let cos = (i as f64 + 0.5) / n as f64;
+            let sin = (1.0 - cos * cos).sqrt();
+            for j in 0..n {
+                let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+                sum += f(&Vec3::new(sin * phi.cos(), sin * phi.sin(), cos));
+            }
+        }
+        sum * solid_angle
+    }
+
+    #[test]
+    fn projected_normals_cover_the_surface() {
+        for roughness in [0.5, 0.8, 1.0] {
+            let ggx = Ggx::from_roughness(roughness);
+            let area = integrate(|m| ggx.d(m) * m.z);
+            assert!((area - 1.0).abs() < 1e-2, "roughness {roughness}: {area}");
+        }
+    }
+
+    #[test]
+    fn visible_normal_pdf_is_normalized() {
+        let ggx = Ggx::from_roughness(0.6);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let total = integrate(|m| ggx.visible_normal_pdf(&wo, m));
+        assert!((total - 1.0).abs() < 1e-2, "{total}");
+    }
+
+    #[test]
+    fn sampled_normals_face_the_viewer() {
+        let ggx = Ggx::from_roughness(0.6);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        for i in 0..32 {
+            for j in 0..32 {
+                let (u1, u2) = ((i as f64 + 0.5) / 32.0, (j as f64 + 0.5) / 32.0);
+                let m = ggx.sample_visible_normal(&wo, u1, u2);
+                assert!((m.norm() - 1.0).abs() < 1e-9);
+                assert!(m.z > 0.0 && wo.dot(&m) > 0.0);
+                assert!(ggx.visible_normal_pdf(&wo, &m) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn fresnel_limits() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(fresnel_dielectric(0.3, 1.0 / 1.5), 1.0);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-12);
+    }
+}
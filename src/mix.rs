@@ -0,0 +1,284 @@
+use rand::random;
+
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::medium::Medium;
+use crate::microfacet::{Frame, Ggx, fresnel_dielectric};
+use crate::spectrum::rgb_to_spectral;
+use crate::texture::{SolidColor, Texture};
+use crate::{Ray, Rc, Vec3, reflect};
+
+// Blends two materials, picking `b` with a probability given by the first
+// channel of the weight texture and `a` otherwise. The pick is made once per
+// hit from its random sample, so a cut out lobe lets light through exactly as
+// often as it is picked.
+#[derive(Debug, Clone)]
+pub struct MixMaterial {
+    a: Rc<dyn Material>,
+    b: Rc<dyn Material>,
+    weight: Rc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Rc<dyn Material>, b: Rc<dyn Material>, weight: f64) -> Self {
+        Self::from_texture(a, b, Rc::new(SolidColor::scalar(weight)))
+    }
+
+    pub fn from_texture(a: Rc<dyn Material>, b: Rc<dyn Material>, weight: Rc<dyn Texture>) -> Self {
+        MixMaterial { a, b, weight }
+    }
+
+    fn weight(&self, hit_record: &HitRecord) -> f64 {
+        self.weight
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+            .x
+            .clamp(0.0, 1.0)
+    }
+
+    // The lobe picked by the random sample of the hit, along with the hit
+    // for it with the sample stretched back to [0, 1) for nested mixtures.
+    // All methods go through this, so a hit is either entirely one lobe or
+    // entirely the other.
+    fn lobe(&self, hit_record: &HitRecord) -> (&Rc<dyn Material>, HitRecord) {
+        let w = self.weight(hit_record);
+        let (lobe, sample) = if hit_record.sample < w {
+            (&self.b, hit_record.sample / w)
+        } else {
+            (&self.a, (hit_record.sample - w) / (1.0 - w))
+        };
+        (
+            lobe,
+            HitRecord {
+                sample,
+                ..hit_record.clone()
+            },
+        )
+    }
+
+    // Mixing a spectral with an RGB material gives a spectral mixture, which
+    // upsamples the values of the RGB one itself.
+    fn lobe_color(&self, lobe: &Rc<dyn Material>, ray: &Ray, color: Color) -> Color {
+        match &ray.wavelengths {
+            Some(wavelengths) if self.is_spectral() && !lobe.is_spectral() => {
+                rgb_to_spectral(&color, wavelengths)
+            }
+            _ => color,
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (lobe, hit_record) = self.lobe(hit_record);
+        let (attenuation, mut scattered) = lobe.scatter(ray_in, &hit_record)?;
+        // The mixture reports a medium when either lobe has one, so the
+        // camera leaves the media to it and a lobe without one passes on
+        // those of the incoming ray here.
+        if lobe.medium().is_none() {
+            scattered.media = ray_in.media;
+            scattered.wavelengths = ray_in.wavelengths;
+        }
+        Some((self.lobe_color(lobe, ray_in, attenuation), scattered))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let (lobe, hit_record) = self.lobe(hit_record);
+        let f = lobe.eval(ray_in, &hit_record, direction)?;
+        Some(self.lobe_color(lobe, ray_in, f))
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let (lobe, hit_record) = self.lobe(hit_record);
+        lobe.pdf(ray_in, &hit_record, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let (lobe, hit_record) = self.lobe(hit_record);
+        lobe.emitted(ray_in, &hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.a.medium().or(self.b.medium())
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.a.is_spectral() || self.b.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        let (lobe, hit_record) = self.lobe(hit_record);
+        lobe.is_transparent(&hit_record)
+    }
+
+    // The ID of the lobe with the larger weight, so the pass doesn't depend
+    // on which lobe the hit picked.
+    fn id(&self, hit_record: &HitRecord) -> usize {
+        if self.weight(hit_record) > 0.5 {
+            self.b.id(hit_record)
+        } else {
+            self.a.id(hit_record)
+        }
+    }
+}
+
+// A clear or tinted dielectric coat over any base material, like varnish or
+// car paint. Light reflects off the coat with its Fresnel reflectance, the
+// rest reaches the base and gets attenuated by the coat again on its way out.
+#[derive(Debug, Clone)]
+pub struct Layered {
+    base: Rc<dyn Material>,
+    refraction_index: f64,
+    distribution: Ggx,
+    // Transmittance through the coat at normal incidence, both ways
+    tint: Color,
+}
+
+impl Layered {
+    pub fn new(base: Rc<dyn Material>, refraction_index: f64, roughness: f64) -> Self {
+        Layered {
+            base,
+            refraction_index,
+            distribution: Ggx::from_roughness(roughness),
+            tint: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    // Fraction of light crossing the coat towards and back from the base.
+    fn transmittance(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let entering = 1.0 - fresnel_dielectric(wo.z, self.refraction_index);
+        if wi.z <= 0.0 {
+            // Transmitted through the base, the coat is only crossed once.
+            return Color::repeat(entering);
+        }
+        let leaving = 1.0 - fresnel_dielectric(wi.z, self.refraction_index);
+        let path = 0.5 * (1.0 / wo.z.max(1e-4) + 1.0 / wi.z.max(1e-4));
+        self.tint.map(|t| t.powf(path)) * (entering * leaving)
+    }
+
+    fn coat_probability(&self, wo: &Vec3) -> f64 {
+        fresnel_dielectric(wo.z, self.refraction_index)
+    }
+
+    fn coat_eval(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let m = (wo + wi).normalize();
+        fresnel_dielectric(wo.dot(&m), self.refraction_index)
+            * self.distribution.d(&m)
+            * self.distribution.g2(wo, wi)
+            / (4.0 * wo.z)
+    }
+
+    fn coat_pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let m = (wo + wi).normalize();
+        self.distribution.visible_normal_pdf(wo, &m) / (4.0 * wo.dot(&m))
+    }
+}
+
+impl Material for Layered {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let coat_probability = self.coat_probability(&wo);
+        let (attenuation, scattered) = if random::<f64>() < coat_probability {
+            let m = self
+                .distribution
+                .sample_visible_normal(&wo, random(), random());
+            let wi = reflect(&-wo, &m);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            let weight = fresnel_dielectric(wo.dot(&m), self.refraction_index)
+                * self.distribution.g2(&wo, &wi)
+                / self.distribution.g1(&wo);
+            // The ray stays outside, in whatever media it already was.
+            let mut reflected = Ray::new(hit_record.p, frame.to_world(&wi));
+            reflected.media = ray_in.media;
+            reflected.wavelengths = ray_in.wavelengths;
+            (Color::repeat(weight / coat_probability), reflected)
+        } else {
+            let (attenuation, scattered) = self.base.scatter(ray_in, hit_record)?;
+            let wi = frame.to_local(&scattered.direction().normalize());
+            (
+                attenuation.component_mul(&self.transmittance(&wo, &wi)) / (1.0 - coat_probability),
+                scattered,
+            )
+        };
+
+        // A base that can be evaluated gets weighed with the whole layered
+        // BSDF, like the mixture above.
+        let direction = scattered.direction();
+        match (
+            self.eval(ray_in, hit_record, &direction),
+            self.pdf(ray_in, hit_record, &direction),
+        ) {
+            (Some(f), pdf) if pdf > 0.0 => Some((f / pdf, scattered)),
+            _ => Some((attenuation, scattered)),
+        }
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let base = self.base.eval(ray_in, hit_record, direction)?;
+        let frame = Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 {
+            return Some(Color::zeros());
+        }
+        Some(
+            base.component_mul(&self.transmittance(&wo, &wi))
+                + Color::repeat(self.coat_eval(&wo, &wi)),
+        )
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let frame = Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let wi = frame.to_local(&direction.normalize());
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        let coat_probability = self.coat_probability(&wo);
+        coat_probability * self.coat_pdf(&wo, &wi)
+            + (1.0 - coat_probability) * self.base.pdf(ray_in, hit_record, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        let cos = hit_record
+            .normal
+            .dot(&-ray_in.direction().normalize())
+            .max(0.0);
+        self.base.emitted(ray_in, hit_record)
+            * (1.0 - fresnel_dielectric(cos, self.refraction_index))
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.base.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.base.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        self.base.is_transparent(hit_record)
+    }
+
+    fn id(&self, hit_record: &HitRecord) -> usize {
+        self.base.id(hit_record)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::Point3;
+use rand::{Rng, SeedableRng};
+
+// Seedable 3D Perlin gradient noise.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    permutation: [u8; 512],
+}
+
+const GRADIENTS: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut table: Vec<u8> = (0..=255).collect();
+        for i in (1..table.len()).rev() {
+            table.swap(i, rng.random_range(0..=i));
+        }
+        let mut permutation = [0; 512];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = table[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let p = &self.permutation;
+        let i = p[(x & 255) as usize] as usize;
+        let i = p[i + (y & 255) as usize] as usize;
+        p[i + (z & 255) as usize] as usize
+    }
+
+    // Noise value roughly in -1..1.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let cell = p.map(f64::floor);
+        let f = p - cell;
+        let fade = f.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+        let (x, y, z) = (cell.x as i64, cell.y as i64, cell.z as i64);
+
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let g = GRADIENTS[self.hash(x + dx, y + dy, z + dz) % 12];
+            let offset = f - Point3::new(dx as f64, dy as f64, dz as f64);
+            let weight = (if dx == 1 { fade.x } else { 1.0 - fade.x })
+                * (if dy == 1 { fade.y } else { 1.0 - fade.y })
+                * (if dz == 1 { fade.z } else { 1.0 - fade.z });
+            value += weight * (g[0] * offset.x + g[1] * offset.y + g[2] * offset.z);
+        }
+        value
+    }
+
+    // Fractal sum of `octaves` noise layers, each twice the frequency and half
+    // the amplitude of the previous one.
+    pub fn fbm(&self, p: &Point3, octaves: usize) -> f64 {
+        let mut value = 0.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut p = *p;
+        for _ in 0..octaves {
+            value += amplitude * self.noise(&p);
+            total += amplitude;
+            amplitude *= 0.5;
+            p *= 2.0;
+        }
+        if total > 0.0 { value / total } else { 0.0 }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, azimuth};
+use crate::material::Material;
+use crate::roots::solve_quadratic;
+
+// A paraboloid y = k (x^2 + z^2) opening towards +y with its vertex at the
+// origin, cut off at y = height where it is `radius` wide. Use `Transformed`
+// to place it in the world.
+#[derive(Debug, Clone)]
+pub struct Paraboloid {
+    radius: f64,
+    height: f64,
+    capped: bool,
+    mat: Rc<dyn Material>,
+}
+
+impl Paraboloid {
+    pub fn new(radius: f64, height: f64, capped: bool, mat: Rc<dyn Material>) -> Self {
+        Paraboloid {
+            radius: radius.max(1e-12),
+            height: height.max(0.0),
+            capped,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = self.height / (self.radius * self.radius);
+        let mut t_range = t_range.clone();
+        let mut hit: Option<HitRecord> = None;
+
+        let roots = solve_quadratic(
+            k * (d.x * d.x + d.z * d.z),
+            2.0 * k * (o.x * d.x + o.z * d.z) - d.y,
+            k * (o.x * o.x + o.z * o.z) - o.y,
+        );
+        for t in roots {
+            let p = ray.at(t);
+            if !t_range.contains(&t) || p.y > self.height {
+                continue;
+            }
+            let outward_normal = Vec3::new(2.0 * k * p.x, -1.0, 2.0 * k * p.z).normalize();
+            t_range.end = t;
+            hit = Some(
+                HitRecord::new(t, p, self.mat.clone(), outward_normal, ray)
+                    .with_uv(azimuth(&p), p.y / self.height.max(1e-12)),
+            );
+        }
+
+        if self.capped && d.y.abs() > 1e-12 {
+            let t = (self.height - o.y) / d.y;
+            let p = ray.at(t);
+            if t_range.contains(&t) && p.x * p.x + p.z * p.z <= self.radius * self.radius {
+                hit = Some(
+                    HitRecord::new(t, p, self.mat.clone(), Vec3::y(), ray).with_uv(
+                        0.5 * (p.x / self.radius + 1.0),
+                        0.5 * (p.z / self.radius + 1.0),
+                    ),
+                );
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(
+            Point3::new(-self.radius, 0.0, -self.radius),
+            Point3::new(self.radius, self.height, self.radius),
+        )
+    }
+}
@@ -0,0 +1,59 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+
+// An infinite plane through `point`. The uv coordinates are distances along
+// two tangent axes of the plane measured from `point`.
+#[derive(Debug, Clone)]
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    mat: Rc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Rc<dyn Material>) -> Self {
+        let normal = normal.normalize();
+        let (bitangent, _) = crate::orthonormal_basis(&normal);
+        let tangent = bitangent.cross(&normal);
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            mat,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let denom = self.normal.dot(&ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(&(self.point - ray.origin())) / denom;
+        if !t_range.contains(&t) {
+            return None;
+        }
+
+        let intersection = ray.at(t);
+        let offset = intersection - self.point;
+        Some(
+            HitRecord::new(t, intersection, self.mat.clone(), self.normal, ray)
+                .with_uv(offset.dot(&self.tangent), offset.dot(&self.bitangent))
+                .with_tangents(self.tangent, self.bitangent),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::infinite()
+    }
+}
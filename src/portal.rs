@@ -0,0 +1,156 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Portal;
+
+pub type Frame = nalgebra::Isometry3<f64>;
+
+// Rays that already went through this many portals in a row are absorbed.
+pub const MAX_PORTAL_DEPTH: usize = 32;
+
+// Shapes are defined in the local frame of the portal: centered at the origin,
+// planar shapes lie in the xy-plane facing +z.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortalShape {
+    Disk { radius: f64 },
+    Rectangle { width: f64, height: f64 },
+    Sphere { radius: f64 },
+}
+
+impl PortalShape {
+    pub fn local_bounding_box(&self) -> Aabb {
+        let half = match *self {
+            PortalShape::Disk { radius } => Vec3::new(radius, radius, 1e-4),
+            PortalShape::Rectangle { width, height } => Vec3::new(width / 2.0, height / 2.0, 1e-4),
+            PortalShape::Sphere { radius } => Vec3::repeat(radius),
+        };
+        Aabb::new(-half, half)
+    }
+
+    pub fn is_planar(&self) -> bool {
+        !matches!(self, PortalShape::Sphere { .. })
+    }
+
+    // Returns the smallest t in t_range and the local outward normal where the
+    // local ray hits the shape.
+    pub fn hit_local(
+        &self,
+        origin: &Vec3,
+        direction: &Vec3,
+        t_range: &std::ops::Range<f64>,
+    ) -> Option<(f64, Vec3)> {
+        match *self {
+            PortalShape::Disk { .. } | PortalShape::Rectangle { .. } => {
+                if direction.z.abs() < 1e-8 {
+                    return None;
+                }
+                let t = -origin.z / direction.z;
+                if !t_range.contains(&t) {
+                    return None;
+                }
+                let p = origin + t * direction;
+                let inside = match *self {
+                    PortalShape::Disk { radius } => p.x * p.x + p.y * p.y <= radius * radius,
+                    PortalShape::Rectangle { width, height } => {
+                        p.x.abs() <= width / 2.0 && p.y.abs() <= height / 2.0
+                    }
+                    PortalShape::Sphere { .. } => unreachable!(),
+                };
+                inside.then_some((t, Vec3::new(0.0, 0.0, 1.0)))
+            }
+            PortalShape::Sphere { radius } => {
+                let a = direction.magnitude_squared();
+                let h = -origin.dot(direction);
+                let c = origin.magnitude_squared() - radius * radius;
+                let discriminant = h * h - a * c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+
+                let sqrt_d = discriminant.sqrt();
+                let mut root = (h - sqrt_d) / a;
+                if !t_range.contains(&root) {
+                    root = (h + sqrt_d) / a;
+                    if !t_range.contains(&root) {
+                        return None;
+                    }
+                }
+                Some((root, (origin + root * direction) / radius))
+            }
+        }
+    }
+}
+
+// A portal surface placed in the world by `frame`, which maps local portal
+// coordinates to world coordinates.
+#[derive(Debug, Clone)]
+pub struct PortalSurface {
+    shape: PortalShape,
+    frame: Frame,
+    mat: Rc<Portal>,
+}
+
+impl PortalSurface {
+    pub fn new(shape: PortalShape, frame: Frame, mat: Rc<Portal>) -> Self {
+        PortalSurface { shape, frame, mat }
+    }
+
+    // Creates two surfaces of the same shape linked to each other. A ray
+    // entering one of them leaves the other with its position and direction
+    // carried over by the rigid transform between the two frames.
+    pub fn new_pair(
+        shape: PortalShape,
+        albedo_a: Color,
+        albedo_b: Color,
+        frame_a: Frame,
+        frame_b: Frame,
+    ) -> (Self, Self) {
+        let (mat_a, mat_b) = Portal::new_linked_pair(shape, albedo_a, albedo_b, frame_a, frame_b);
+        (
+            PortalSurface::new(shape, frame_a, Rc::new(mat_a)),
+            PortalSurface::new(shape, frame_b, Rc::new(mat_b)),
+        )
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+impl Hittable for PortalSurface {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let inverse = self.frame.inverse();
+        let origin = inverse.transform_point(&ray.origin().into()).coords;
+        let direction = inverse.transform_vector(&ray.direction());
+
+        let (t, local_normal) = self.shape.hit_local(&origin, &direction, t_range)?;
+        let outward_normal = self.frame.transform_vector(&local_normal);
+        Some(HitRecord::new(
+            t,
+            ray.at(t),
+            self.mat.clone(),
+            outward_normal,
+            ray,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let corners = self
+            .shape
+            .local_bounding_box()
+            .corners()
+            .map(|c| self.frame.transform_point(&c.into()).coords);
+        Aabb::from_points(&corners)
+    }
+}
+
+// Builds a frame at `center` whose local +z axis points along `normal` and
+// local +y axis is as close as possible to `up`.
+pub fn frame_facing(center: Point3, normal: Vec3, up: Vec3) -> Frame {
+    let rotation = nalgebra::UnitQuaternion::face_towards(&normal, &up);
+    Frame::from_parts(nalgebra::Translation3::from(center), rotation)
+}
@@ -0,0 +1,448 @@
+use std::f64::consts::PI;
+
+use rand::random;
+
+use crate::color::{Color, luminance};
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet::{Frame, Ggx, fresnel_dielectric, refract_through};
+use crate::texture::{SolidColor, Texture};
+use crate::{Ray, Rc, Vec3, reflect};
+
+// Inputs of the principled material. Scalar parameters are read from the
+// first channel of their texture.
+#[derive(Debug, Clone)]
+pub struct PrincipledSettings {
+    pub base_color: Rc<dyn Texture>,
+    pub metallic: Rc<dyn Texture>,
+    pub roughness: Rc<dyn Texture>,
+    // Scales the Fresnel reflectance of the dielectric part, 1 being physically
+    // correct for `ior`.
+    pub specular: Rc<dyn Texture>,
+    pub ior: f64,
+    pub clearcoat: Rc<dyn Texture>,
+    pub clearcoat_roughness: Rc<dyn Texture>,
+    pub sheen: Rc<dyn Texture>,
+    pub transmission: Rc<dyn Texture>,
+    pub emission: Rc<dyn Texture>,
+    pub emission_strength: f64,
+}
+
+impl Default for PrincipledSettings {
+    fn default() -> Self {
+        PrincipledSettings {
+            base_color: Rc::new(SolidColor::new(Color::repeat(0.8))),
+            metallic: Rc::new(SolidColor::scalar(0.0)),
+            roughness: Rc::new(SolidColor::scalar(0.5)),
+            specular: Rc::new(SolidColor::scalar(1.0)),
+            ior: 1.5,
+            clearcoat: Rc::new(SolidColor::scalar(0.0)),
+            clearcoat_roughness: Rc::new(SolidColor::scalar(0.03)),
+            sheen: Rc::new(SolidColor::new(Color::zeros())),
+            transmission: Rc::new(SolidColor::scalar(0.0)),
+            emission: Rc::new(SolidColor::new(Color::zeros())),
+            emission_strength: 1.0,
+        }
+    }
+}
+
+// Values of the MTL statements that affect a principled material, including
+// the PBR extension statements (Pr, Pm, Ps, Pc, Pcr).
+#[derive(Debug, Clone, Copy)]
+pub struct MtlParameters {
+    pub kd: Color,
+    pub ks: Color,
+    pub ke: Color,
+    pub ns: f64,
+    pub ni: f64,
+    pub d: f64,
+    pub pr: Option<f64>,
+    pub pm: Option<f64>,
+    pub ps: Option<f64>,
+    pub pc: Option<f64>,
+    pub pcr: Option<f64>,
+}
+
+impl Default for MtlParameters {
+    fn default() -> Self {
+        MtlParameters {
+            kd: Color::repeat(0.8),
+            ks: Color::zeros(),
+            ke: Color::zeros(),
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            pr: None,
+            pm: None,
+            ps: None,
+            pc: None,
+            pcr: None,
+        }
+    }
+}
+
+impl From<MtlParameters> for PrincipledSettings {
+    fn from(mtl: MtlParameters) -> Self {
+        // Without Pr, convert the Phong exponent through its Beckmann
+        // equivalent roughness.
+        let roughness = mtl
+            .pr
+            .unwrap_or_else(|| (2.0 / (mtl.ns.max(0.0) + 2.0)).sqrt().sqrt());
+        // Without Pm, Ks is the specular color of a dielectric.
+        let specular = if mtl.pm.is_some() {
+            1.0
+        } else {
+            let f0 = ((mtl.ni - 1.0) / (mtl.ni + 1.0)).powi(2).max(1e-4);
+            (luminance(&mtl.ks) / f0).clamp(0.0, 1.0)
+        };
+        PrincipledSettings {
+            base_color: Rc::new(SolidColor::new(mtl.kd)),
+            metallic: Rc::new(SolidColor::scalar(mtl.pm.unwrap_or(0.0))),
+            roughness: Rc::new(SolidColor::scalar(roughness)),
+            specular: Rc::new(SolidColor::scalar(specular)),
+            ior: mtl.ni,
+            clearcoat: Rc::new(SolidColor::scalar(mtl.pc.unwrap_or(0.0))),
+            clearcoat_roughness: Rc::new(SolidColor::scalar(mtl.pcr.unwrap_or(0.03))),
+            sheen: Rc::new(SolidColor::scalar(mtl.ps.unwrap_or(0.0))),
+            transmission: Rc::new(SolidColor::scalar(1.0 - mtl.d.clamp(0.0, 1.0))),
+            emission: Rc::new(SolidColor::new(mtl.ke)),
+            emission_strength: 1.0,
+        }
+    }
+}
+
+// The glTF metallic-roughness factors together with the KHR_materials_ior,
+// _transmission, _specular, _clearcoat, _sheen and _emissive_strength
+// extensions. Defaults follow the glTF specification.
+#[derive(Debug, Clone, Copy)]
+pub struct GltfParameters {
+    pub base_color_factor: Color,
+    pub metallic_factor: f64,
+    pub roughness_factor: f64,
+    pub emissive_factor: Color,
+    pub emissive_strength: f64,
+    pub ior: f64,
+    pub transmission_factor: f64,
+    pub specular_factor: f64,
+    pub clearcoat_factor: f64,
+    pub clearcoat_roughness_factor: f64,
+    pub sheen_color_factor: Color,
+}
+
+impl Default for GltfParameters {
+    fn default() -> Self {
+        GltfParameters {
+            base_color_factor: Color::repeat(1.0),
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: Color::zeros(),
+            emissive_strength: 1.0,
+            ior: 1.5,
+            transmission_factor: 0.0,
+            specular_factor: 1.0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            sheen_color_factor: Color::zeros(),
+        }
+    }
+}
+
+impl From<GltfParameters> for PrincipledSettings {
+    fn from(gltf: GltfParameters) -> Self {
+        PrincipledSettings {
+            base_color: Rc::new(SolidColor::new(gltf.base_color_factor)),
+            metallic: Rc::new(SolidColor::scalar(gltf.metallic_factor)),
+            roughness: Rc::new(SolidColor::scalar(gltf.roughness_factor)),
+            specular: Rc::new(SolidColor::scalar(gltf.specular_factor)),
+            ior: gltf.ior,
+            clearcoat: Rc::new(SolidColor::scalar(gltf.clearcoat_factor)),
+            clearcoat_roughness: Rc::new(SolidColor::scalar(gltf.clearcoat_roughness_factor)),
+            sheen: Rc::new(SolidColor::new(gltf.sheen_color_factor)),
+            transmission: Rc::new(SolidColor::scalar(gltf.transmission_factor)),
+            emission: Rc::new(SolidColor::new(gltf.emissive_factor)),
+            emission_strength: gltf.emissive_strength,
+        }
+    }
+}
+
+// A Disney style uber material layering a clearcoat over a blend of a GGX
+// metal and a dielectric base, which reflects specularly and either
+// transmits or scatters diffusely with sheen below the specular layer.
+#[derive(Debug, Clone)]
+pub struct Principled {
+    settings: PrincipledSettings,
+}
+
+impl Principled {
+    pub fn new(settings: PrincipledSettings) -> Self {
+        Principled { settings }
+    }
+}
+
+// Parameters evaluated at a hit point, with everything needed to evaluate
+// and sample the lobes in the local shading frame.
+struct Lobes {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    clearcoat: f64,
+    sheen: Color,
+    transmission: f64,
+    distribution: Ggx,
+    clearcoat_distribution: Ggx,
+    // Index of refraction behind the surface over the one in front of it
+    eta: f64,
+    // Probabilities of sampling the diffuse, specular, transmission and
+    // clearcoat lobes
+    weights: [f64; 4],
+}
+
+const CLEARCOAT_ETA: f64 = 1.5;
+
+impl Lobes {
+    fn new(settings: &PrincipledSettings, hit_record: &HitRecord, wo: &Vec3) -> Self {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        let scalar = |texture: &Rc<dyn Texture>| texture.value(u, v, p).x.clamp(0.0, 1.0);
+        let base_color = settings.base_color.value(u, v, p);
+        let metallic = scalar(&settings.metallic);
+        let roughness = scalar(&settings.roughness);
+        let specular = scalar(&settings.specular);
+        let clearcoat = scalar(&settings.clearcoat);
+        let sheen = settings.sheen.value(u, v, p);
+        let transmission = scalar(&settings.transmission);
+        let eta = if hit_record.front_face {
+            settings.ior
+        } else {
+            1.0 / settings.ior
+        };
+
+        let mut lobes = Lobes {
+            base_color,
+            metallic,
+            roughness,
+            specular,
+            clearcoat,
+            sheen,
+            transmission,
+            distribution: Ggx::from_roughness(roughness),
+            clearcoat_distribution: Ggx::from_roughness(scalar(&settings.clearcoat_roughness)),
+            eta,
+            weights: [0.0; 4],
+        };
+
+        // Rough estimates of how much each lobe reflects towards `wo`, with a
+        // floor so that no lobe that contributes is never sampled.
+        let dielectric = 1.0 - metallic;
+        let specular_f = lobes.dielectric_fresnel(wo.z);
+        let coat = lobes.coat_transmittance(wo.z);
+        let albedo = luminance(&base_color).max(0.05);
+        let floor = |enabled: bool, weight: f64| if enabled { weight.max(0.02) } else { 0.0 };
+        lobes.weights = [
+            coat * floor(
+                dielectric * (1.0 - transmission) > 0.0,
+                dielectric
+                    * (1.0 - transmission)
+                    * (1.0 - specular_f)
+                    * (albedo + luminance(&sheen)),
+            ),
+            coat * floor(
+                metallic > 0.0 || specular > 0.0,
+                metallic * albedo + dielectric * specular_f,
+            ),
+            coat * floor(
+                dielectric * transmission > 0.0,
+                dielectric * transmission * (1.0 - specular_f) * albedo,
+            ),
+            floor(
+                clearcoat > 0.0,
+                clearcoat * fresnel_dielectric(wo.z, CLEARCOAT_ETA),
+            ),
+        ];
+        let total: f64 = lobes.weights.iter().sum();
+        if total > 0.0 {
+            lobes.weights.iter_mut().for_each(|w| *w /= total);
+        } else {
+            lobes.weights = [1.0, 0.0, 0.0, 0.0];
+        }
+        lobes
+    }
+
+    fn dielectric_fresnel(&self, cos: f64) -> f64 {
+        (self.specular * fresnel_dielectric(cos, self.eta)).min(1.0)
+    }
+
+    // Fraction of light passing through the clearcoat.
+    fn coat_transmittance(&self, cos: f64) -> f64 {
+        1.0 - self.clearcoat * fresnel_dielectric(cos, CLEARCOAT_ETA)
+    }
+
+    // Half vector of a refraction from `wo` to `wi`, on the side of `wo`.
+    fn transmission_half_vector(&self, wo: &Vec3, wi: &Vec3) -> Option<Vec3> {
+        let m = wo + wi * self.eta;
+        if m.norm_squared() == 0.0 {
+            return None;
+        }
+        let m = m.normalize();
+        let m = if m.z < 0.0 { -m } else { m };
+        (wo.dot(&m) > 0.0 && wi.dot(&m) < 0.0).then_some(m)
+    }
+
+    // BSDF times the cosine term.
+    fn eval(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Color::zeros();
+        }
+        let dielectric = 1.0 - self.metallic;
+        let coat = self.coat_transmittance(wo.z);
+
+        if wi.z < 0.0 {
+            if self.transmission == 0.0 {
+                return Color::zeros();
+            }
+            let Some(m) = self.transmission_half_vector(wo, wi) else {
+                return Color::zeros();
+            };
+            let fresnel = self.dielectric_fresnel(wo.dot(&m));
+            let d = self.distribution.d(&m);
+            let g = self.distribution.g2(wo, wi);
+            let denom = wo.dot(&m) + self.eta * wi.dot(&m);
+            let btdf = (1.0 - fresnel) * d * g * (wi.dot(&m) * wo.dot(&m)).abs()
+                / (wo.z * denom * denom)
+                * self.eta
+                * self.eta;
+            return self.base_color * (coat * dielectric * self.transmission * btdf);
+        }
+
+        let h = (wo + wi).normalize();
+        let cos_d = wi.dot(&h);
+
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let retro = (1.0 + (fd90 - 1.0) * (1.0 - wi.z).powi(5))
+            * (1.0 + (fd90 - 1.0) * (1.0 - wo.z).powi(5));
+        let diffuse = (self.base_color * retro / PI + self.sheen * (1.0 - cos_d).powi(5))
+            * (dielectric * (1.0 - self.transmission) * (1.0 - self.dielectric_fresnel(wo.z)));
+
+        let metal_fresnel =
+            self.base_color + (Color::repeat(1.0) - self.base_color) * (1.0 - cos_d).powi(5);
+        let fresnel = metal_fresnel * self.metallic
+            + Color::repeat(dielectric * self.dielectric_fresnel(wo.dot(&h)));
+        let specular =
+            fresnel * self.distribution.d(&h) * self.distribution.g2(wo, wi) / (4.0 * wo.z * wi.z);
+
+        let clearcoat = self.clearcoat
+            * fresnel_dielectric(wo.dot(&h), CLEARCOAT_ETA)
+            * self.clearcoat_distribution.d(&h)
+            * self.clearcoat_distribution.g2(wo, wi)
+            / (4.0 * wo.z * wi.z);
+
+        ((diffuse + specular) * coat + Color::repeat(clearcoat)) * wi.z
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+        let [diffuse, specular, transmission, clearcoat] = self.weights;
+
+        if wi.z < 0.0 {
+            let Some(m) = self.transmission_half_vector(wo, wi) else {
+                return 0.0;
+            };
+            let denom = wo.dot(&m) + self.eta * wi.dot(&m);
+            return transmission
+                * self.distribution.visible_normal_pdf(wo, &m)
+                * self.eta
+                * self.eta
+                * wi.dot(&m).abs()
+                / (denom * denom);
+        }
+
+        let h = (wo + wi).normalize();
+        diffuse * wi.z / PI
+            + specular * self.distribution.visible_normal_pdf(wo, &h) / (4.0 * wo.dot(&h))
+            + clearcoat * self.clearcoat_distribution.visible_normal_pdf(wo, &h)
+                / (4.0 * wo.dot(&h))
+    }
+
+    fn sample(&self, wo: &Vec3) -> Option<Vec3> {
+        let [diffuse, specular, transmission, _] = self.weights;
+        let choice: f64 = random();
+        let wi = if choice < diffuse {
+            let (x, y) = concentric_disk(random(), random());
+            Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+        } else if choice < diffuse + specular {
+            let m = self
+                .distribution
+                .sample_visible_normal(wo, random(), random());
+            reflect(&-wo, &m)
+        } else if choice < diffuse + specular + transmission {
+            let m = self
+                .distribution
+                .sample_visible_normal(wo, random(), random());
+            refract_through(wo, &m, self.eta)?
+        } else {
+            let m = self
+                .clearcoat_distribution
+                .sample_visible_normal(wo, random(), random());
+            reflect(&-wo, &m)
+        };
+        (wi.z != 0.0).then_some(wi)
+    }
+}
+
+fn concentric_disk(u1: f64, u2: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+impl Principled {
+    fn local(&self, ray_in: &Ray, hit_record: &HitRecord) -> (Frame, Vec3, Lobes) {
+        let frame = Frame::from_normal(&hit_record.normal);
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+        let lobes = Lobes::new(&self.settings, hit_record, &wo);
+        (frame, wo, lobes)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (frame, wo, lobes) = self.local(ray_in, hit_record);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let wi = lobes.sample(&wo)?;
+        let pdf = lobes.pdf(&wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = lobes.eval(&wo, &wi) / pdf;
+        Some((attenuation, Ray::new(hit_record.p, frame.to_world(&wi))))
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let (frame, wo, lobes) = self.local(ray_in, hit_record);
+        Some(lobes.eval(&wo, &frame.to_local(&direction.normalize())))
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let (frame, wo, lobes) = self.local(ray_in, hit_record);
+        lobes.pdf(&wo, &frame.to_local(&direction.normalize()))
+    }
+
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.settings
+            .emission
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+            * self.settings.emission_strength
+    }
+}
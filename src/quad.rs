@@ -0,0 +1,73 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+
+// A parallelogram spanned by `u` and `v` from the corner `q`.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    normal: Vec3,
+    d: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Rc<dyn Material>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        Quad {
+            q,
+            u,
+            v,
+            w: n / n.dot(&n),
+            normal,
+            d: normal.dot(&q),
+            mat,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let denom = self.normal.dot(&ray.direction());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(&ray.origin())) / denom;
+        if !t_range.contains(&t) {
+            return None;
+        }
+
+        let intersection = ray.at(t);
+        let planar_hitpt = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(t, intersection, self.mat.clone(), self.normal, ray)
+                .with_uv(alpha, beta)
+                .with_tangents(self.u, self.v),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&[
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ])
+        .padded(1e-4)
+    }
+}
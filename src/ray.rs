@@ -1,15 +1,31 @@
 use crate::Point3;
 use crate::Vec3;
+use crate::medium::MediumStack;
+use crate::spectrum::Wavelengths;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
+    pub portal_depth: usize,
+    pub media: MediumStack,
+    // Wavelengths the ray carries in spectral mode
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
     pub fn new(orig: Point3, dir: Vec3) -> Self {
-        Ray { orig, dir }
+        Ray {
+            orig,
+            dir,
+            portal_depth: 0,
+            media: MediumStack::new(),
+            wavelengths: None,
+        }
+    }
+
+    pub fn with_origin(self, orig: Point3) -> Self {
+        Ray { orig, ..self }
     }
 
     pub fn origin(&self) -> Point3 {
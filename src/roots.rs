@@ -0,0 +1,230 @@
+// Real roots of low degree polynomials, returned in ascending order.
+
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return Vec::new();
+        }
+        return vec![-c / b];
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    // Avoid cancellation by never subtracting nearly equal values.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (r0, r1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    if r0 < r1 { vec![r0, r1] } else { vec![r1, r0] }
+}
+
+// Roots of x^3 + a x^2 + b x + c.
+fn solve_normalized_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let sub = a / 3.0;
+
+    let mut roots = if d.abs() < 1e-14 {
+        if q.abs() < 1e-14 {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::PI / 3.0).cos(),
+            -t * (phi - std::f64::consts::PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in &mut roots {
+        *root -= sub;
+    }
+    roots
+}
+
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return solve_quadratic(b, c, d);
+    }
+    let mut roots = solve_normalized_cubic(b / a, c / a, d / a);
+    for root in &mut roots {
+        *root = polish(&[a, b, c, d], *root);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Ferrari's method, with every root refined by Newton iterations on the
+// original polynomial since the closed form loses a lot of precision.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return solve_cubic(b, c, d, e);
+    }
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // Substitute x = y - b/4 to eliminate the cubic term:
+    // y^4 + p y^2 + q y + r = 0
+    let sq_b = b * b;
+    let p = -3.0 / 8.0 * sq_b + c;
+    let q = 1.0 / 8.0 * sq_b * b - 0.5 * b * c + d;
+    let r = -3.0 / 256.0 * sq_b * sq_b + 1.0 / 16.0 * sq_b * c - 0.25 * b * d + e;
+
+    let mut roots = if r.abs() < 1e-14 {
+        // y (y^3 + p y + q) = 0
+        let mut roots = solve_normalized_cubic(0.0, p, q);
+        roots.push(0.0);
+        roots
+    } else {
+        // Solve the resolvent cubic and use one of its roots to split the
+        // quartic into two quadratics.
+        let z = solve_normalized_cubic(-0.5 * p, -r, 0.5 * r * p - 0.125 * q * q)[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < 1e-14 {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if v.abs() < 1e-14 {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return Vec::new();
+        };
+
+        let q_sign = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic(1.0, q_sign, z - u);
+        roots.extend(solve_quadratic(1.0, -q_sign, z + u));
+        roots
+    };
+
+    let coefficients = [1.0, b, c, d, e];
+    for root in &mut roots {
+        *root = polish(&coefficients, *root - 0.25 * b);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+fn polish(coefficients: &[f64], mut x: f64) -> f64 {
+    let evaluate = |x: f64| {
+        coefficients
+            .iter()
+            .fold((0.0, 0.0), |(value, derivative), &coefficient| {
+                (value * x + coefficient, derivative * x + value)
+            })
+    };
+
+    let (mut value, mut derivative) = evaluate(x);
+    for _ in 0..4 {
+        let next = x - value / derivative;
+        if !next.is_finite() {
+            break;
+        }
+        let (next_value, next_derivative) = evaluate(next);
+        if next_value.abs() >= value.abs() {
+            break;
+        }
+        (x, value, derivative) = (next, next_value, next_derivative);
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Coefficients of `a` times the product of (x - root), highest degree
+    // first.
+    fn expand(a: f64, roots: &[f64]) -> Vec<f64> {
+        roots.iter().fold(vec![a], |coefficients, root| {
+            let mut next = coefficients.clone();
+            next.push(0.0);
+            for (i, c) in coefficients.iter().enumerate() {
+                next[i + 1] -= root * c;
+            }
+            next
+        })
+    }
+
+    fn assert_roots(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} vs {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-9 * e.abs().max(1.0),
+                "{actual:?} vs {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_without_cancellation() {
+        assert_roots(&solve_quadratic(1.0, -1e8, 1.0), &[1e-8, 1e8]);
+        assert_roots(&solve_quadratic(2.0, 0.0, -8.0), &[-2.0, 2.0]);
+        assert_roots(&solve_quadratic(1.0, 0.0, 1.0), &[]);
+        assert_roots(&solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        let c = expand(1.0, &[1.0, -2.0, 5.0]);
+        assert_roots(&solve_cubic(c[0], c[1], c[2], c[3]), &[-2.0, 1.0, 5.0]);
+        // x^3 - 1 has a single real root
+        assert_roots(&solve_cubic(1.0, 0.0, 0.0, -1.0), &[1.0]);
+    }
+
+    #[test]
+    fn quartic_with_four_roots() {
+        let c = expand(1.0, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(c, vec![1.0, -10.0, 35.0, -50.0, 24.0]);
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+
+        let expected = [-0.5, 0.25, 3.0, 7.0];
+        let c = expand(-2.5, &expected);
+        assert_roots(&solve_quartic(c[0], c[1], c[2], c[3], c[4]), &expected);
+    }
+
+    #[test]
+    fn quartic_with_two_or_no_roots() {
+        // (x^2 - 1)(x^2 + 1)
+        assert_roots(&solve_quartic(1.0, 0.0, 0.0, 0.0, -1.0), &[-1.0, 1.0]);
+        assert_roots(&solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn quartic_with_zero_root() {
+        let c = expand(1.0, &[-3.0, 0.0, 0.5, 2.0]);
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[-3.0, 0.0, 0.5, 2.0],
+        );
+    }
+
+    #[test]
+    fn quartic_falls_back_to_cubic() {
+        let c = expand(1.0, &[1.0, -2.0, 5.0]);
+        assert_roots(
+            &solve_quartic(0.0, c[0], c[1], c[2], c[3]),
+            &[-2.0, 1.0, 5.0],
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use rand::Rng;
+
+// A piecewise constant distribution over `0..weights.len()` proportional to
+// the given non-negative weights.
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    weights: Vec<f64>,
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    pub fn new(weights: Vec<f64>) -> Self {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        let mut total = 0.0;
+        cdf.push(0.0);
+        for weight in &weights {
+            total += weight.max(0.0);
+            cdf.push(total);
+        }
+        Distribution1D {
+            weights,
+            cdf,
+            total,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    // Probability of picking bin `index`.
+    pub fn probability(&self, index: usize) -> f64 {
+        if self.total > 0.0 {
+            self.weights[index].max(0.0) / self.total
+        } else {
+            1.0 / self.len() as f64
+        }
+    }
+
+    // Picks a bin and returns it with the position of the sample inside the
+    // bin remapped to 0..1.
+    pub fn sample(&self, rng: &mut impl Rng) -> (usize, f64) {
+        let target = rng.random::<f64>() * self.total;
+        if self.total <= 0.0 {
+            let x = rng.random::<f64>() * self.len() as f64;
+            let index = (x as usize).min(self.len() - 1);
+            return (index, x - index as f64);
+        }
+
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= target)
+            .saturating_sub(1)
+            .min(self.len() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            ((target - self.cdf[index]) / width).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        (index, offset)
+    }
+}
+
+// A piecewise constant distribution over a `width` x `height` grid, sampled
+// by picking a row and then a column within it.
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(weights: &[f64], width: usize, height: usize) -> Self {
+        let rows: Vec<Distribution1D> = weights
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.total()).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    // Returns a point in the unit square and its density with respect to area.
+    pub fn sample(&self, rng: &mut impl Rng) -> ((f64, f64), f64) {
+        let (y, offset_y) = self.marginal.sample(rng);
+        let (x, offset_x) = self.rows[y].sample(rng);
+        let u = (x as f64 + offset_x) / self.rows[y].len() as f64;
+        let v = (y as f64 + offset_y) / self.marginal.len() as f64;
+        ((u, v), self.pdf(u, v))
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let height = self.marginal.len();
+        let y = ((v * height as f64) as usize).min(height - 1);
+        let width = self.rows[y].len();
+        let x = ((u * width as f64) as usize).min(width - 1);
+        self.marginal.probability(y) * self.rows[y].probability(x) * (width * height) as f64
+    }
+}
@@ -0,0 +1,20 @@
+use crate::Rc;
+use crate::environment::{Environment, Gradient};
+use crate::hittable::Hittable;
+
+#[derive(Debug, Clone)]
+pub struct Scene<W: Hittable> {
+    pub world: W,
+    pub environment: Rc<dyn Environment>,
+}
+
+impl<W: Hittable> Scene<W> {
+    pub fn new(world: W, environment: Rc<dyn Environment>) -> Self {
+        Scene { world, environment }
+    }
+
+    // A scene lit by the default sky gradient.
+    pub fn with_sky(world: W) -> Self {
+        Self::new(world, Rc::new(Gradient::default()))
+    }
+}
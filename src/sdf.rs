@@ -0,0 +1,255 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+
+// A signed distance field: negative inside, positive outside. `distance` may
+// underestimate the distance to the surface but must never overestimate it,
+// otherwise sphere tracing steps through the surface.
+#[cfg(feature = "rayon")]
+pub trait Sdf: std::fmt::Debug + Send + Sync {
+    fn distance(&self, p: &Point3) -> f64;
+}
+
+#[cfg(not(feature = "rayon"))]
+pub trait Sdf: std::fmt::Debug {
+    fn distance(&self, p: &Point3) -> f64;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SdfSphere {
+    pub radius: f64,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: &Point3) -> f64 {
+        p.magnitude() - self.radius
+    }
+}
+
+// An axis-aligned box with its edges rounded off by `radius`. A radius of 0
+// gives a sharp box.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundedBox {
+    pub half_extents: Vec3,
+    pub radius: f64,
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = p.abs() - self.half_extents + Vec3::repeat(self.radius);
+        q.sup(&Vec3::zeros()).magnitude() + q.max().min(0.0) - self.radius
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SdfTorus {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: &Point3) -> f64 {
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (ring * ring + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+// Distance estimate of the power-n Mandelbulb fractal, which fits in a sphere
+// of radius ~1.2 for the usual power of 8.
+#[derive(Debug, Clone, Copy)]
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: usize,
+}
+
+impl Default for Mandelbulb {
+    fn default() -> Self {
+        Mandelbulb {
+            power: 8.0,
+            iterations: 12,
+        }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: &Point3) -> f64 {
+        let mut z = *p;
+        let mut dr = 1.0;
+        let mut r = z.magnitude();
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+            let theta = (z.z / r).clamp(-1.0, 1.0).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = r.powf(self.power)
+                * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + p;
+            r = z.magnitude();
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Translate<S: Sdf> {
+    pub inner: S,
+    pub offset: Vec3,
+}
+
+impl<S: Sdf> Sdf for Translate<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        self.inner.distance(&(p - self.offset))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scale<S: Sdf> {
+    pub inner: S,
+    pub factor: f64,
+}
+
+impl<S: Sdf> Sdf for Scale<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        self.inner.distance(&(p / self.factor)) * self.factor
+    }
+}
+
+// Union of two fields blended together within a distance of `k`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let (d1, d2) = (self.a.distance(p), self.b.distance(p));
+        if self.k <= 0.0 {
+            return d1.min(d2);
+        }
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0.0, 1.0);
+        d2 * (1.0 - h) + d1 * h - self.k * h * (1.0 - h)
+    }
+}
+
+// Twists the field around the y axis by `rate` radians per unit of height.
+#[derive(Debug, Clone, Copy)]
+pub struct Twist<S: Sdf> {
+    pub inner: S,
+    pub rate: f64,
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let (sin, cos) = (self.rate * p.y).sin_cos();
+        let q = Point3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+        // Twisting stretches space further away from the axis, so shrink the
+        // distance accordingly to stay conservative.
+        let stretch = (1.0 + (self.rate * (p.x * p.x + p.z * p.z).sqrt()).powi(2)).sqrt();
+        self.inner.distance(&q) / stretch
+    }
+}
+
+// Repeats the field infinitely with the given period along each axis. A
+// period of 0 leaves that axis unrepeated.
+#[derive(Debug, Clone, Copy)]
+pub struct Repeat<S: Sdf> {
+    pub inner: S,
+    pub period: Vec3,
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: &Point3) -> f64 {
+        let q = p.zip_map(&self.period, |x, period| {
+            if period > 0.0 {
+                x - period * (x / period).round()
+            } else {
+                x
+            }
+        });
+        self.inner.distance(&q)
+    }
+}
+
+const MAX_STEPS: usize = 512;
+const SURFACE_EPSILON: f64 = 1e-5;
+
+// Renders a signed distance field by sphere tracing inside `bounds`.
+#[derive(Debug, Clone)]
+pub struct SdfObject<S: Sdf> {
+    sdf: S,
+    bounds: Aabb,
+    mat: Rc<dyn Material>,
+}
+
+impl<S: Sdf> SdfObject<S> {
+    pub fn new(sdf: S, bounds: Aabb, mat: Rc<dyn Material>) -> Self {
+        SdfObject { sdf, bounds, mat }
+    }
+
+    pub fn normal(&self, p: &Point3) -> Vec3 {
+        // Tetrahedral central differences need only four evaluations.
+        let h = SURFACE_EPSILON;
+        let k = [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ];
+        let gradient: Vec3 = k.iter().map(|k| k * self.sdf.distance(&(p + k * h))).sum();
+        if gradient.norm_squared() > 0.0 {
+            gradient.normalize()
+        } else {
+            Vec3::y()
+        }
+    }
+}
+
+impl<S: Sdf + 'static> Hittable for SdfObject<S> {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let span = self.bounds.hit(ray, t_range)?;
+        let length = ray.direction().magnitude();
+
+        let mut t = span.start;
+        let mut d = self.sdf.distance(&ray.at(t));
+        // Rays leaving the surface start right on it, move off it first so the
+        // surface they start on isn't reported again.
+        if d.abs() < 2.0 * SURFACE_EPSILON {
+            t += 4.0 * SURFACE_EPSILON / length;
+            d = self.sdf.distance(&ray.at(t));
+        }
+        let side = d.signum();
+
+        for _ in 0..MAX_STEPS {
+            if t >= span.end {
+                return None;
+            }
+            let d_side = side * d;
+            if d_side < SURFACE_EPSILON {
+                let p = ray.at(t);
+                return Some(HitRecord::new(t, p, self.mat.clone(), self.normal(&p), ray));
+            }
+            t += d_side / length;
+            d = self.sdf.distance(&ray.at(t));
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
@@ -0,0 +1,184 @@
+use std::f64::consts::PI;
+
+use rand::random;
+
+use crate::color::{Color, luminance};
+use crate::hittable::HitRecord;
+use crate::material::{Material, cosine_pdf};
+use crate::medium::Medium;
+use crate::microfacet::fresnel_dielectric;
+use crate::texture::{SolidColor, Texture};
+use crate::{Ray, Rc, Vec3, near_zero, random_unit_vec3, reflect};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    // Each hit is transparent with a probability of one minus the alpha,
+    // which averages out to partial coverage.
+    Stochastic,
+    // Hits with an alpha below the cutoff are transparent, like glTF masks.
+    Cutoff(f64),
+}
+
+// Cuts out parts of a surface where the first channel of the alpha texture
+// is low, for leaves and fences modeled as flat quads.
+#[derive(Debug, Clone)]
+pub struct AlphaMask {
+    inner: Rc<dyn Material>,
+    alpha: Rc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl AlphaMask {
+    pub fn new(inner: Rc<dyn Material>, alpha: Rc<dyn Texture>, mode: AlphaMode) -> Self {
+        AlphaMask { inner, alpha, mode }
+    }
+}
+
+impl Material for AlphaMask {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        self.inner.scatter(ray_in, hit_record)
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        self.inner.eval(ray_in, hit_record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        self.inner.pdf(ray_in, hit_record, direction)
+    }
+
+    fn emitted(&self, ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        self.inner.emitted(ray_in, hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.inner.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.inner.is_spectral()
+    }
+
+    fn is_transparent(&self, hit_record: &HitRecord) -> bool {
+        let alpha = self
+            .alpha
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+            .x;
+        let cut = match self.mode {
+            AlphaMode::Stochastic => random::<f64>() >= alpha,
+            AlphaMode::Cutoff(cutoff) => alpha < cutoff,
+        };
+        cut || self.inner.is_transparent(hit_record)
+    }
+
+    fn id(&self, hit_record: &HitRecord) -> usize {
+        self.inner.id(hit_record)
+    }
+}
+
+// A dielectric sheet with no thickness, like a window pane or a bubble
+// wall. Light is either reflected or passes straight through, accounting for
+// the reflections between both faces.
+#[derive(Debug, Clone, Copy)]
+pub struct ThinDielectric {
+    refraction_index: f64,
+}
+
+impl ThinDielectric {
+    pub fn new(refraction_index: f64) -> Self {
+        ThinDielectric { refraction_index }
+    }
+}
+
+impl Material for ThinDielectric {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let unit_direction = ray_in.direction().normalize();
+        let cos_theta = -unit_direction.dot(&hit_record.normal).min(1.0);
+        let r = fresnel_dielectric(cos_theta, self.refraction_index);
+        // Sum of all the light bouncing back and forth inside the sheet
+        let reflectance = 2.0 * r / (1.0 + r);
+
+        let direction = if reflectance > random() {
+            reflect(&unit_direction, &hit_record.normal)
+        } else {
+            unit_direction
+        };
+        Some((Color::new(1.0, 1.0, 1.0), Ray::new(hit_record.p, direction)))
+    }
+}
+
+// A thin diffuse sheet like paper or a leaf, scattering light diffusely
+// back on the side it came from and through to the other side.
+#[derive(Debug, Clone)]
+pub struct DiffuseTransmission {
+    reflectance: Rc<dyn Texture>,
+    transmittance: Rc<dyn Texture>,
+}
+
+impl DiffuseTransmission {
+    pub fn new(reflectance: Color, transmittance: Color) -> Self {
+        Self::from_textures(
+            Rc::new(SolidColor::new(reflectance)),
+            Rc::new(SolidColor::new(transmittance)),
+        )
+    }
+
+    pub fn from_textures(reflectance: Rc<dyn Texture>, transmittance: Rc<dyn Texture>) -> Self {
+        DiffuseTransmission {
+            reflectance,
+            transmittance,
+        }
+    }
+
+    fn colors(&self, hit_record: &HitRecord) -> (Color, Color) {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        (
+            self.reflectance.value(u, v, p),
+            self.transmittance.value(u, v, p),
+        )
+    }
+
+    // Probability of sampling the reflected side.
+    fn reflect_probability(reflectance: &Color, transmittance: &Color) -> f64 {
+        let (r, t) = (luminance(reflectance), luminance(transmittance));
+        if r + t > 0.0 { r / (r + t) } else { 0.5 }
+    }
+}
+
+impl Material for DiffuseTransmission {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (reflectance, transmittance) = self.colors(hit_record);
+        let probability = Self::reflect_probability(&reflectance, &transmittance);
+        let (side, attenuation) = if random::<f64>() < probability {
+            (hit_record.normal, reflectance / probability)
+        } else {
+            (-hit_record.normal, transmittance / (1.0 - probability))
+        };
+
+        let mut direction = side + random_unit_vec3();
+        if near_zero(&direction) {
+            direction = side;
+        }
+        let mut scattered = Ray::new(hit_record.p, direction);
+        scattered.media = ray_in.media;
+        Some((attenuation, scattered))
+    }
+
+    fn eval(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> Option<Color> {
+        let (reflectance, transmittance) = self.colors(hit_record);
+        let cos = hit_record.normal.dot(&direction.normalize());
+        let color = if cos >= 0.0 {
+            reflectance
+        } else {
+            transmittance
+        };
+        Some(color * cos.abs() / PI)
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vec3) -> f64 {
+        let (reflectance, transmittance) = self.colors(hit_record);
+        let probability = Self::reflect_probability(&reflectance, &transmittance);
+        probability * cosine_pdf(&hit_record.normal, direction)
+            + (1.0 - probability) * cosine_pdf(&-hit_record.normal, direction)
+    }
+}
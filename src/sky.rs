@@ -0,0 +1,215 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::Vec3;
+use crate::color::Color;
+use crate::environment::Environment;
+use crate::spectrum::{LAMBDA_MAX, LAMBDA_MIN, blackbody, cie_xyz, xyz_to_linear_srgb};
+
+// Angular radius of the sun as seen from earth.
+const SUN_ANGULAR_RADIUS: f64 = 0.2665 * PI / 180.0;
+// Luminance of the sun outside the atmosphere, in kcd/m^2 like the sky.
+const SUN_LUMINANCE: f64 = 1.6e6;
+
+// The analytic daylight model by Preetham, Shirley and Smits, with a sun disk
+// that can be sampled directly.
+#[derive(Debug, Clone)]
+pub struct PhysicalSky {
+    sun_direction: Vec3,
+    turbidity: f64,
+    // Radiance per kcd/m^2 of luminance
+    intensity: f64,
+    perez_y: [f64; 5],
+    perez_x: [f64; 5],
+    perez_yy: [f64; 5],
+    // Zenith values divided by the Perez function towards the zenith
+    zenith: Vec3,
+    sun_radiance: Color,
+    sun_cos_radius: f64,
+}
+
+impl PhysicalSky {
+    // `turbidity` ranges from about 2 for a very clear sky to 10 for haze.
+    pub fn new(sun_direction: Vec3, turbidity: f64) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity.clamp(1.7, 10.0);
+        let theta_s = sun_direction.y.clamp(-1.0, 1.0).acos().min(PI / 2.0);
+
+        let perez_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let perez_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let perez_yy = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let theta = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+        let dot = |coefficients: [f64; 4]| -> f64 {
+            coefficients.iter().zip(theta).map(|(c, t)| c * t).sum()
+        };
+        let zenith_x = t * t * dot([0.00166, -0.00375, 0.00209, 0.0])
+            + t * dot([-0.02903, 0.06377, -0.03202, 0.00394])
+            + dot([0.11693, -0.21196, 0.06052, 0.25886]);
+        let zenith_y = t * t * dot([0.00275, -0.00610, 0.00317, 0.0])
+            + t * dot([-0.04214, 0.08970, -0.04153, 0.00516])
+            + dot([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let zenith = Vec3::new(
+            zenith_luminance / perez(&perez_y, 0.0, theta_s),
+            zenith_x / perez(&perez_x, 0.0, theta_s),
+            zenith_y / perez(&perez_yy, 0.0, theta_s),
+        );
+
+        PhysicalSky {
+            sun_direction,
+            turbidity: t,
+            intensity: 0.025,
+            perez_y,
+            perez_x,
+            perez_yy,
+            zenith,
+            sun_radiance: sun_color(theta_s, t) * SUN_LUMINANCE,
+            sun_cos_radius: SUN_ANGULAR_RADIUS.cos(),
+        }
+    }
+
+    // Sun position by elevation above the horizon and azimuth from -z
+    // towards +x, both in degrees.
+    pub fn from_angles(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let direction = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        Self::new(direction, turbidity)
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f64 {
+        self.turbidity
+    }
+
+    fn sky_radiance(&self, direction: &Vec3) -> Color {
+        // Below the horizon keep the horizon color, standing in for the ground.
+        let cos_theta = direction.y.max(0.01);
+        let theta = cos_theta.acos();
+        let gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let luminance = self.zenith.x * perez(&self.perez_y, theta, gamma);
+        let x = self.zenith.y * perez(&self.perez_x, theta, gamma);
+        let y = self.zenith.z * perez(&self.perez_yy, theta, gamma);
+        if y <= 0.0 {
+            return Color::zeros();
+        }
+        let xyz = Vec3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        xyz_to_linear_srgb(&xyz).sup(&Color::zeros())
+    }
+
+    fn sun_visible(&self) -> bool {
+        self.sun_direction.y > -SUN_ANGULAR_RADIUS
+    }
+
+    fn sun_solid_angle(&self) -> f64 {
+        2.0 * PI * (1.0 - self.sun_cos_radius)
+    }
+}
+
+// Perez et al. sky luminance distribution for a view `theta` from the zenith
+// and `gamma` from the sun.
+fn perez(coefficients: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / theta.cos().max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+// Color of sunlight after passing through the atmosphere, with a luminance of
+// 1 before attenuation. Accounts for Rayleigh and aerosol scattering.
+fn sun_color(theta_s: f64, turbidity: f64) -> Color {
+    let beta = 0.04608 * turbidity - 0.04586;
+    let theta_deg = theta_s.to_degrees();
+    let optical_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_deg).max(1e-3).powf(-1.253));
+
+    let (attenuated, unattenuated) = (0..=80)
+        .map(|i| {
+            let lambda = LAMBDA_MIN + i as f64 * (LAMBDA_MAX - LAMBDA_MIN) / 80.0;
+            let micrometers = lambda / 1000.0;
+            let rayleigh = 0.008735 * micrometers.powf(-4.08);
+            let aerosol = beta * micrometers.powf(-1.3);
+            let transmittance = (-optical_mass * (rayleigh + aerosol)).exp();
+            let xyz = cie_xyz(lambda) * blackbody(lambda, 5778.0);
+            (xyz * transmittance, xyz)
+        })
+        .fold((Vec3::zeros(), Vec3::zeros()), |(a, b), (x, y)| {
+            (a + x, b + y)
+        });
+
+    xyz_to_linear_srgb(&(attenuated / unattenuated.y)).sup(&Color::zeros())
+}
+
+impl Environment for PhysicalSky {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let direction = direction.normalize();
+        let sky = self.sky_radiance(&direction);
+        if self.sun_visible() && direction.dot(&self.sun_direction) >= self.sun_cos_radius {
+            (sky + self.sun_radiance) * self.intensity
+        } else {
+            sky * self.intensity
+        }
+    }
+
+    // Half of the samples go towards the sun disk, the rest uniformly over the
+    // sphere for the sky.
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        let mut rng = rand::rng();
+        let direction = if self.sun_visible() && rng.random::<f64>() < 0.5 {
+            let cos_theta = 1.0 - rng.random::<f64>() * (1.0 - self.sun_cos_radius);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = rng.random_range(0.0..2.0 * PI);
+            let (u, v) = crate::orthonormal_basis(&self.sun_direction);
+            self.sun_direction * cos_theta + (u * phi.cos() + v * phi.sin()) * sin_theta
+        } else {
+            crate::random_unit_vec3()
+        };
+        Some((direction, self.pdf(&direction)))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let uniform = 1.0 / (4.0 * PI);
+        if !self.sun_visible() {
+            return uniform;
+        }
+        let in_sun = direction.normalize().dot(&self.sun_direction) >= self.sun_cos_radius;
+        0.5 * uniform
+            + if in_sun {
+                0.5 / self.sun_solid_angle()
+            } else {
+                0.0
+            }
+    }
+}
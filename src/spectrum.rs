@@ -0,0 +1,184 @@
+use crate::color::Color;
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma_low } else { sigma_high };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 color matching functions at `lambda` nanometers, using the
+// multi-lobe fit by Wyman, Sloan and Shirley.
+pub fn cie_xyz(lambda: f64) -> crate::Vec3 {
+    let x = 1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+        + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+        + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8);
+    crate::Vec3::new(x, y, z)
+}
+
+pub fn xyz_to_linear_srgb(xyz: &crate::Vec3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z,
+        -0.9692660 * xyz.x + 1.8760108 * xyz.y + 0.0415560 * xyz.z,
+        0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z,
+    )
+}
+
+// Spectral radiance of a black body at `temperature` kelvin, up to a
+// constant factor.
+pub fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+    let l = lambda * 1e-9;
+    2.0 * H * C * C / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0))
+}
+
+// Linear sRGB color of a black body at `temperature` kelvin, scaled to a
+// luminance of 1. Colors outside of the sRGB gamut are clipped.
+pub fn blackbody_color(temperature: f64) -> Color {
+    let xyz: crate::Vec3 = (0..=80)
+        .map(|i| {
+            let lambda = LAMBDA_MIN + i as f64 * (LAMBDA_MAX - LAMBDA_MIN) / 80.0;
+            cie_xyz(lambda) * blackbody(lambda, temperature)
+        })
+        .sum();
+    let rgb = xyz_to_linear_srgb(&(xyz / xyz.y)).sup(&Color::zeros());
+    rgb / crate::color::luminance(&rgb)
+}
+
+// Wavelengths carried by a path in spectral mode: a uniformly sampled hero
+// wavelength and two more spaced evenly around the visible range from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavelengths {
+    pub lambda: crate::Vec3,
+    // Set once the path went through a dispersive interface, which only the
+    // hero wavelength follows.
+    pub hero_only: bool,
+}
+
+impl Wavelengths {
+    pub fn sample(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let lambda = crate::Vec3::from_fn(|i, _| LAMBDA_MIN + (u + i as f64 / 3.0).fract() * range);
+        Wavelengths {
+            lambda,
+            hero_only: false,
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda.x
+    }
+}
+
+// Smooth blue, green and red basis spectra summing up to a flat spectrum,
+// chosen so that they map back close to the sRGB primaries.
+fn rgb_basis(lambda: f64) -> Color {
+    let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+    let blue = 1.0 - sigmoid((lambda - 495.0) / 8.0);
+    let red = sigmoid((lambda - 585.0) / 8.0);
+    Color::new(red, 1.0 - red - blue, blue)
+}
+
+// Upsamples a linear sRGB color to a spectrum and evaluates it at each of
+// the wavelengths. White maps to a flat spectrum.
+pub fn rgb_to_spectral(rgb: &Color, wavelengths: &Wavelengths) -> crate::Vec3 {
+    wavelengths
+        .lambda
+        .map(|lambda| rgb_to_spectrum(rgb, lambda))
+}
+
+pub fn rgb_to_spectrum(rgb: &Color, lambda: f64) -> f64 {
+    rgb.dot(&rgb_basis(lambda))
+}
+
+// Linear sRGB color of a flat spectrum of unit value.
+fn flat_spectrum_rgb() -> Color {
+    static WHITE: std::sync::OnceLock<Color> = std::sync::OnceLock::new();
+    *WHITE.get_or_init(|| {
+        const STEPS: usize = 400;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+        let xyz: crate::Vec3 = (0..STEPS)
+            .map(|i| cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step) * step)
+            .sum();
+        xyz_to_linear_srgb(&xyz)
+    })
+}
+
+// Converts the radiance a path carried at `wavelengths` to an estimate of
+// the linear sRGB color, white balanced so a flat spectrum is white.
+pub fn spectral_to_rgb(values: &crate::Vec3, wavelengths: &Wavelengths) -> Color {
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let xyz: crate::Vec3 = (0..3)
+        .map(|i| cie_xyz(wavelengths.lambda[i]) * values[i])
+        .sum::<crate::Vec3>()
+        * range
+        / 3.0;
+    xyz_to_linear_srgb(&xyz).component_div(&flat_spectrum_rgb())
+}
+
+// Linear sRGB color of a reflectance spectrum lit by a flat spectrum,
+// integrated with `samples` wavelengths.
+pub fn reflectance_to_rgb(reflectance: impl Fn(f64) -> f64, samples: usize) -> Color {
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / samples as f64;
+    let xyz: crate::Vec3 = (0..samples)
+        .map(|i| {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+            cie_xyz(lambda) * reflectance(lambda) * step
+        })
+        .sum();
+    xyz_to_linear_srgb(&xyz).component_div(&flat_spectrum_rgb())
+}
+
+// Wavelength of the Fraunhofer d line, where refractive indices of optical
+// glasses are usually given.
+pub const D_LINE: f64 = 587.56;
+
+// Index of refraction as a function of the wavelength.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    // n = a + b / lambda^2 with lambda in micrometers
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i) with lambda in
+    // micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn fused_silica() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    pub fn refraction_index(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1e-3).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+}
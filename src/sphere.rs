@@ -1,7 +1,11 @@
+use std::f64::consts::PI;
+
 use crate::Point3;
 use crate::Ray;
 use crate::Rc;
-use crate::hittable::{HitRecord, Hittable};
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, azimuth};
 use crate::material::Material;
 
 #[derive(Debug, Clone)]
@@ -43,12 +47,26 @@ impl Hittable for Sphere {
             }
         }
 
-        Some(HitRecord::new(
-            root,
-            ray.at(root),
-            self.mat.clone(),
-            (ray.at(root) - self.center) / self.radius,
-            ray,
-        ))
+        let p = ray.at(root);
+        let n = (p - self.center) / self.radius;
+        // Latitude and longitude, u going around the y axis and v from the
+        // bottom pole to the top one.
+        let sin_theta = (1.0 - n.y * n.y).max(0.0).sqrt();
+        let dpdu = 2.0 * PI * self.radius * Vec3::new(n.z, 0.0, -n.x);
+        let dpdv = if sin_theta > 1e-8 {
+            PI * self.radius * Vec3::new(-n.x * n.y / sin_theta, sin_theta, -n.z * n.y / sin_theta)
+        } else {
+            Vec3::zeros()
+        };
+        Some(
+            HitRecord::new(root, p, self.mat.clone(), n, ray)
+                .with_uv(azimuth(&n), (-n.y).clamp(-1.0, 1.0).acos() / PI)
+                .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = crate::Vec3::repeat(self.radius);
+        Aabb::new(self.center - r, self.center + r)
     }
 }
@@ -0,0 +1,273 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, SeedableRng};
+
+use crate::Vec3;
+use crate::color::Color;
+use crate::environment::{Environment, direction_to_face};
+use crate::noise::Perlin;
+use crate::spectrum::blackbody_color;
+
+#[derive(Debug, Clone, Copy)]
+pub struct StarfieldSettings {
+    pub seed: u64,
+    pub star_count: usize,
+    // Angular size (standard deviation of the star profile) in degrees. Stars
+    // are spread over a fixed solid angle rather than over pixels so they
+    // stay sharp no matter the resolution or how much they get lensed.
+    pub star_size: f64,
+    // Peak radiance of a magnitude 0 star.
+    pub brightness: f64,
+    pub brightest_magnitude: f64,
+    pub faintest_magnitude: f64,
+}
+
+impl Default for StarfieldSettings {
+    fn default() -> Self {
+        StarfieldSettings {
+            seed: 0,
+            star_count: 20000,
+            star_size: 0.02,
+            brightness: 20.0,
+            brightest_magnitude: -1.0,
+            faintest_magnitude: 6.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NebulaSettings {
+    pub seed: u64,
+    // Frequency of the largest noise features over the unit sphere.
+    pub scale: f64,
+    pub octaves: usize,
+    pub intensity: f64,
+    // Fraction of the sky left empty, in 0..1.
+    pub coverage_threshold: f64,
+    pub colors: [Color; 2],
+}
+
+impl Default for NebulaSettings {
+    fn default() -> Self {
+        NebulaSettings {
+            seed: 1,
+            scale: 2.0,
+            octaves: 6,
+            intensity: 0.15,
+            coverage_threshold: 0.55,
+            colors: [Color::new(0.9, 0.2, 0.5), Color::new(0.2, 0.4, 1.0)],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Star {
+    direction: Vec3,
+    // Radiance integrated over the solid angle of the star
+    flux: Color,
+}
+
+#[derive(Debug, Clone)]
+struct Nebula {
+    settings: NebulaSettings,
+    density: Perlin,
+    hue: Perlin,
+}
+
+impl Nebula {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let p = direction * self.settings.scale;
+        let value = 0.5 + self.density.fbm(&p, self.settings.octaves);
+        let threshold = self.settings.coverage_threshold.clamp(0.0, 0.999);
+        let density = ((value - threshold) / (1.0 - threshold)).clamp(0.0, 1.0);
+        if density == 0.0 {
+            return Color::zeros();
+        }
+        let mix = (0.5 + self.hue.fbm(&(p * 0.5), 3)).clamp(0.0, 1.0);
+        let [a, b] = self.settings.colors;
+        (a * (1.0 - mix) + b * mix) * density * density * self.settings.intensity
+    }
+}
+
+// Stars are gaussian profiles truncated at this many standard deviations.
+const STAR_EXTENT: f64 = 3.0;
+
+// A deterministic procedural star catalog with an optional nebula behind it.
+#[derive(Debug, Clone)]
+pub struct Starfield {
+    stars: Vec<Star>,
+    sigma: f64,
+    // Stars overlapping each cell of a cube map grid over all directions
+    grid_size: usize,
+    grid: Vec<Vec<u32>>,
+    distribution: crate::sampling::Distribution1D,
+    nebula: Option<Nebula>,
+}
+
+impl Starfield {
+    pub fn new(settings: StarfieldSettings) -> Self {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(settings.seed);
+        let sigma = settings.star_size.to_radians().max(1e-6);
+        let solid_angle = 2.0 * PI * sigma * sigma;
+
+        // The number of stars brighter than magnitude m grows as 10^(0.6 m).
+        let (m_min, m_max) = (
+            settings.brightest_magnitude,
+            settings
+                .faintest_magnitude
+                .max(settings.brightest_magnitude),
+        );
+        let floor = 10f64.powf(-0.6 * (m_max - m_min));
+        let stars: Vec<Star> = (0..settings.star_count)
+            .map(|_| {
+                let direction = random_direction(&mut rng);
+                let magnitude = m_max + (rng.random::<f64>() * (1.0 - floor) + floor).log10() / 0.6;
+                // Cool stars are far more common than hot ones.
+                let temperature = 2500.0 * 12f64.powf(rng.random::<f64>().powi(2));
+                let flux = settings.brightness
+                    * solid_angle
+                    * 10f64.powf(-0.4 * magnitude)
+                    * blackbody_color(temperature);
+                Star { direction, flux }
+            })
+            .collect();
+
+        let grid_size = ((settings.star_count as f64 / 12.0).sqrt().ceil() as usize).clamp(1, 512);
+        let mut grid = vec![Vec::new(); 6 * grid_size * grid_size];
+        for (index, star) in stars.iter().enumerate() {
+            let (u, v) = crate::orthonormal_basis(&star.direction);
+            let reach = STAR_EXTENT * sigma;
+            let mut cells: Vec<usize> = (0..9)
+                .map(|i| {
+                    let angle = i as f64 * PI / 4.0;
+                    let offset = if i == 8 {
+                        Vec3::zeros()
+                    } else {
+                        reach * (angle.cos() * u + angle.sin() * v)
+                    };
+                    grid_cell(grid_size, &(star.direction + offset))
+                })
+                .collect();
+            cells.sort_unstable();
+            cells.dedup();
+            for cell in cells {
+                grid[cell].push(index as u32);
+            }
+        }
+
+        let distribution = crate::sampling::Distribution1D::new(
+            stars
+                .iter()
+                .map(|star| crate::color::luminance(&star.flux))
+                .collect(),
+        );
+
+        Starfield {
+            stars,
+            sigma,
+            grid_size,
+            grid,
+            distribution,
+            nebula: None,
+        }
+    }
+
+    pub fn with_nebula(mut self, settings: NebulaSettings) -> Self {
+        self.nebula = Some(Nebula {
+            settings,
+            density: Perlin::new(settings.seed),
+            hue: Perlin::new(settings.seed.wrapping_add(1)),
+        });
+        self
+    }
+
+    // Normalized truncated gaussian over the sphere, accurate for small
+    // angular sizes.
+    fn kernel(&self, star: &Star, direction: &Vec3) -> f64 {
+        let cos = star.direction.dot(direction).clamp(-1.0, 1.0);
+        let angle_sq = 2.0 * (1.0 - cos);
+        let sigma_sq = self.sigma * self.sigma;
+        if angle_sq > STAR_EXTENT * STAR_EXTENT * sigma_sq {
+            return 0.0;
+        }
+        let truncation = 1.0 - (-0.5 * STAR_EXTENT * STAR_EXTENT).exp();
+        (-0.5 * angle_sq / sigma_sq).exp() / (2.0 * PI * sigma_sq * truncation)
+    }
+
+    fn nearby_stars(&self, direction: &Vec3) -> impl Iterator<Item = &Star> {
+        self.grid[grid_cell(self.grid_size, direction)]
+            .iter()
+            .map(|&index| &self.stars[index as usize])
+    }
+
+    // Probability of light sampling picking a star instead of the nebula.
+    fn star_probability(&self) -> f64 {
+        match (&self.nebula, self.stars.is_empty()) {
+            (_, true) => 0.0,
+            (None, false) => 1.0,
+            (Some(_), false) => 0.5,
+        }
+    }
+}
+
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    let z: f64 = rng.random_range(-1.0..=1.0);
+    let phi = rng.random_range(0.0..2.0 * PI);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+fn grid_cell(grid_size: usize, direction: &Vec3) -> usize {
+    let (face, a, b) = direction_to_face(direction);
+    let x = (((a + 1.0) / 2.0 * grid_size as f64) as usize).min(grid_size - 1);
+    let y = (((b + 1.0) / 2.0 * grid_size as f64) as usize).min(grid_size - 1);
+    (face * grid_size + y) * grid_size + x
+}
+
+impl Environment for Starfield {
+    fn radiance(&self, direction: &Vec3) -> Color {
+        let direction = direction.normalize();
+        let stars: Color = self
+            .nearby_stars(&direction)
+            .map(|star| star.flux * self.kernel(star, &direction))
+            .sum();
+        match &self.nebula {
+            Some(nebula) => stars + nebula.radiance(&direction),
+            None => stars,
+        }
+    }
+
+    fn sample(&self) -> Option<(Vec3, f64)> {
+        let mut rng = rand::rng();
+        let direction = if rng.random::<f64>() < self.star_probability() {
+            let (index, _) = self.distribution.sample(&mut rng);
+            let star = &self.stars[index];
+            let truncation = 1.0 - (-0.5 * STAR_EXTENT * STAR_EXTENT).exp();
+            let angle = self.sigma * (-2.0 * (1.0 - rng.random::<f64>() * truncation).ln()).sqrt();
+            let phi = rng.random_range(0.0..2.0 * PI);
+            let (u, v) = crate::orthonormal_basis(&star.direction);
+            (star.direction * angle.cos() + (u * phi.cos() + v * phi.sin()) * angle.sin())
+                .normalize()
+        } else {
+            random_direction(&mut rng)
+        };
+        let pdf = self.pdf(&direction);
+        (pdf > 0.0).then_some((direction, pdf))
+    }
+
+    fn pdf(&self, direction: &Vec3) -> f64 {
+        let direction = direction.normalize();
+        let star_probability = self.star_probability();
+        let stars: f64 = if star_probability > 0.0 {
+            let total = self.distribution.total();
+            self.nearby_stars(&direction)
+                .map(|star| {
+                    crate::color::luminance(&star.flux) / total * self.kernel(star, &direction)
+                })
+                .sum()
+        } else {
+            0.0
+        };
+        star_probability * stars + (1.0 - star_probability) / (4.0 * PI)
+    }
+}
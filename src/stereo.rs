@@ -0,0 +1,151 @@
+use crate::{
+    camera::{Camera, CameraSettings, ImageSettings, Projection, QualitySettings},
+    color::write_color,
+    hittable::Hittable,
+    scene::Scene,
+};
+use log::info;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    // Both eyes look along the same direction, converging at infinity
+    Parallel,
+    // Both eyes are rotated to converge at the look-at point
+    ToeIn,
+    // Omni-directional stereo: every ray of an equirectangular panorama
+    // starts on the viewing circle, so the stereo holds up in all directions
+    OmniDirectional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    // Left eye on the left, right eye on the right
+    SideBySide,
+    // Left eye on top, right eye below
+    TopBottom,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StereoSettings {
+    pub mode: StereoMode,
+    pub layout: StereoLayout,
+    // Distance between the eyes in world units
+    pub interpupillary_distance: f64,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        StereoSettings {
+            mode: StereoMode::Parallel,
+            layout: StereoLayout::SideBySide,
+            interpupillary_distance: 0.064,
+        }
+    }
+}
+
+// Renders a left and right eye view and packs them into one image. The image
+// settings describe a single eye.
+#[derive(Debug, Clone)]
+pub struct StereoCamera {
+    left: Camera,
+    right: Camera,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(
+        image_settings: ImageSettings,
+        quality_settings: QualitySettings,
+        camera_settings: CameraSettings,
+        stereo_settings: StereoSettings,
+    ) -> Self {
+        let half_ipd = stereo_settings.interpupillary_distance / 2.0;
+        let eye = |side: f64| match stereo_settings.mode {
+            StereoMode::Parallel | StereoMode::ToeIn => {
+                let w = (camera_settings.camera_center - camera_settings.camera_lookat).normalize();
+                let u = camera_settings.camera_vup.cross(&w).normalize();
+                let offset = side * half_ipd * u;
+                let camera_lookat = match stereo_settings.mode {
+                    StereoMode::Parallel => camera_settings.camera_lookat + offset,
+                    _ => camera_settings.camera_lookat,
+                };
+                let settings = CameraSettings {
+                    camera_center: camera_settings.camera_center + offset,
+                    camera_lookat,
+                    ..camera_settings.clone()
+                };
+                Camera::new(image_settings, quality_settings, settings)
+            }
+            StereoMode::OmniDirectional => {
+                assert!(
+                    camera_settings.projection == Projection::Equirectangular,
+                    "omni-directional stereo requires an equirectangular projection"
+                );
+                Camera::new(image_settings, quality_settings, camera_settings.clone())
+                    .with_eye_offset(side * half_ipd)
+            }
+        };
+
+        StereoCamera {
+            left: eye(-1.0),
+            right: eye(1.0),
+            layout: stereo_settings.layout,
+        }
+    }
+
+    // Size of the composite image
+    pub fn image_size(&self) -> (usize, usize) {
+        let (width, height) = (self.left.image_width(), self.left.image_height());
+        match self.layout {
+            StereoLayout::SideBySide => (2 * width, height),
+            StereoLayout::TopBottom => (width, 2 * height),
+        }
+    }
+
+    pub fn render<T: std::io::Write, W: Hittable>(
+        &self,
+        file: &mut T,
+        scene: &Scene<W>,
+    ) -> std::io::Result<()> {
+        let (width, height) = self.image_size();
+        let (eye_width, eye_height) = (self.left.image_width(), self.left.image_height());
+        writeln!(file, "P3\n{width} {height}\n255")?;
+        for y in 0..height {
+            info!("Scanlines remaining: {}", height - y);
+            for x in 0..width {
+                let (camera, x, y) = match self.layout {
+                    StereoLayout::SideBySide if x >= eye_width => (&self.right, x - eye_width, y),
+                    StereoLayout::TopBottom if y >= eye_height => (&self.right, x, y - eye_height),
+                    _ => (&self.left, x, y),
+                };
+                write_color(file, camera.pixel_color(x, y, scene))?;
+            }
+        }
+        info!("Done.");
+        Ok(())
+    }
+
+    #[cfg(feature = "image")]
+    pub fn render_to_imgbuf<W: Hittable>(
+        &self,
+        scene: &Scene<W>,
+    ) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
+        use image::GenericImage;
+
+        let (width, height) = self.image_size();
+        let (x, y) = match self.layout {
+            StereoLayout::SideBySide => (self.left.image_width() as u32, 0),
+            StereoLayout::TopBottom => (0, self.left.image_height() as u32),
+        };
+        let mut imgbuf = image::ImageBuffer::new(width as u32, height as u32);
+        info!("Rendering left eye");
+        imgbuf
+            .copy_from(&self.left.render_to_imgbuf(scene), 0, 0)
+            .unwrap();
+        info!("Rendering right eye");
+        imgbuf
+            .copy_from(&self.right.render_to_imgbuf(scene), x, y)
+            .unwrap();
+        imgbuf
+    }
+}
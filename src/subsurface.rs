@@ -0,0 +1,131 @@
+use crate::Ray;
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::material::{Dielectric, Material};
+use crate::medium::Medium;
+
+// Translucent materials like skin, wax or marble. The surface is a smooth
+// dielectric boundary and light that gets through it does a random walk in
+// a scattering medium filling the object until it leaves again.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    boundary: Dielectric,
+}
+
+impl Subsurface {
+    // `albedo` is the color of the object seen as a whole, `mean_free_path`
+    // the average distance light travels in between scattering events.
+    pub fn new(albedo: Color, mean_free_path: Color, refraction_index: f64) -> Self {
+        let extinction = mean_free_path.map(|d| 1.0 / d.max(1e-8));
+        let scattering = albedo
+            .map(single_scattering_albedo)
+            .component_mul(&extinction);
+        Subsurface {
+            boundary: Dielectric::new(refraction_index)
+                .with_absorption(extinction - scattering)
+                .with_scattering(scattering, 0.0),
+        }
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f64) -> Self {
+        let medium = self.boundary.medium().copied().unwrap();
+        self.boundary = self.boundary.with_scattering(medium.scattering, anisotropy);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.boundary = self.boundary.with_priority(priority);
+        self
+    }
+}
+
+// Single scattering albedo giving a multiple scattering albedo of `albedo`
+// in a semi-infinite medium, using the fit by Chiang et al.
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    let a = albedo.clamp(0.0, 1.0);
+    1.0 - (4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt()).powi(2)
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        self.boundary.scatter(ray_in, hit_record)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.boundary.medium()
+    }
+
+    fn is_spectral(&self) -> bool {
+        self.boundary.is_spectral()
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(not(feature = "rayon"), allow(clippy::arc_with_non_send_sync))]
+mod tests {
+    use super::*;
+    use crate::aov::Aov;
+    use crate::camera::{Camera, CameraSettings, ImageSettings, Projection, QualitySettings};
+    use crate::environment::ConstantEnvironment;
+    use crate::lens::Aperture;
+    use crate::scene::Scene;
+    use crate::sphere::Sphere;
+    use crate::{Point3, Rc, Vec3};
+
+    #[test]
+    fn albedo_inversion_keeps_the_extremes() {
+        assert!(single_scattering_albedo(0.0).abs() < 1e-3);
+        assert!((single_scattering_albedo(1.0) - 1.0).abs() < 1e-3);
+        let samples: Vec<f64> = (0..=10)
+            .map(|i| single_scattering_albedo(i as f64 / 10.0))
+            .collect();
+        assert!(samples.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    // Without absorption every random walk leaves the object again, so in
+    // a white environment the object disappears. The walks don't count
+    // against the bounces, so a low max depth is enough.
+    #[test]
+    fn white_furnace() {
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let material = Subsurface::new(white, Vec3::new(0.5, 0.7, 1.0), 1.33);
+        let scene = Scene::new(
+            Sphere::new(Point3::zeros(), 1.0, Rc::new(material)),
+            Rc::new(ConstantEnvironment::new(white)),
+        );
+        let camera = Camera::new(
+            ImageSettings {
+                image_width: 4,
+                aspect_ratio: 1.0,
+            },
+            QualitySettings {
+                samples_per_pixel: 256,
+                max_depth: 20,
+                light_sampling: false,
+                spectral: false,
+            },
+            CameraSettings {
+                projection: Projection::Perspective,
+                vfov: 10.0,
+                focus_dist: 5.0,
+                defocus_angle: 0.0,
+                camera_center: Point3::new(0.0, 0.0, 5.0),
+                camera_lookat: Point3::zeros(),
+                camera_vup: Vec3::y(),
+                aperture: Aperture::default(),
+                lens_system: None,
+                f_stop: None,
+            },
+        );
+        let aovs = camera.render_aovs(&scene, &[Aov::Color, Aov::Albedo]);
+        let mean = |aov| {
+            let layer = aovs.layer(aov).unwrap();
+            layer.iter().map(|&c| c as f64).sum::<f64>() / layer.len() as f64
+        };
+        let color = mean(Aov::Color);
+        assert!((color - 1.0).abs() < 0.03, "{color}");
+        // The boundary passes all light on
+        let albedo = mean(Aov::Albedo);
+        assert!((albedo - 1.0).abs() < 1e-9, "{albedo}");
+    }
+}
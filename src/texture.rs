@@ -0,0 +1,177 @@
+use crate::Point3;
+use crate::Rc;
+use crate::color::Color;
+
+#[cfg(feature = "rayon")]
+pub trait Texture: std::fmt::Debug + Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+#[cfg(not(feature = "rayon"))]
+pub trait Texture: std::fmt::Debug {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        SolidColor { color }
+    }
+
+    // A texture with `value` in every channel, for scalar parameters.
+    pub fn scalar(value: f64) -> Self {
+        SolidColor {
+            color: Color::repeat(value),
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.color
+    }
+}
+
+// A 3D checker pattern in world space, alternating between two textures.
+#[derive(Debug, Clone)]
+pub struct CheckerTexture {
+    scale: f64,
+    even: Rc<dyn Texture>,
+    odd: Rc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self {
+        CheckerTexture { scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let cell = (p * self.scale).map(f64::floor);
+        if (cell.x + cell.y + cell.z) as i64 % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Broadcasts one channel of another texture to all channels, e.g. to read
+// roughness from the green channel of a glTF metallic-roughness texture.
+#[derive(Debug, Clone)]
+pub struct ChannelTexture {
+    inner: Rc<dyn Texture>,
+    channel: usize,
+}
+
+impl ChannelTexture {
+    pub fn new(inner: Rc<dyn Texture>, channel: usize) -> Self {
+        assert!(channel < 3, "channel must be 0, 1 or 2");
+        ChannelTexture { inner, channel }
+    }
+}
+
+impl Texture for ChannelTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        Color::repeat(self.inner.value(u, v, p)[self.channel])
+    }
+}
+
+// Another texture multiplied by a constant factor, like glTF factors applied
+// to their textures.
+#[derive(Debug, Clone)]
+pub struct ScaledTexture {
+    inner: Rc<dyn Texture>,
+    factor: Color,
+}
+
+impl ScaledTexture {
+    pub fn new(inner: Rc<dyn Texture>, factor: Color) -> Self {
+        ScaledTexture { inner, factor }
+    }
+}
+
+impl Texture for ScaledTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.inner.value(u, v, p).component_mul(&self.factor)
+    }
+}
+
+// An image looked up by uv coordinates, repeating outside of 0..1 and with
+// v = 0 at the bottom of the image.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count must match size");
+        ImageTexture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // Loads an image holding colors, converting it from sRGB to linear.
+    #[cfg(feature = "image")]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> image::ImageResult<Self> {
+        Self::load_with(path, |c| srgb_to_linear(c as f64 / 255.0))
+    }
+
+    // Loads an image holding data such as roughness or normals, which is
+    // used as is.
+    #[cfg(feature = "image")]
+    pub fn load_linear<P: AsRef<std::path::Path>>(path: P) -> image::ImageResult<Self> {
+        Self::load_with(path, |c| c as f64 / 255.0)
+    }
+
+    #[cfg(feature = "image")]
+    fn load_with<P: AsRef<std::path::Path>>(
+        path: P,
+        convert: impl Fn(u8) -> f64,
+    ) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_rgb8();
+        let pixels = image
+            .pixels()
+            .map(|p| Color::new(convert(p[0]), convert(p[1]), convert(p[2])))
+            .collect();
+        Ok(Self::new(
+            image.width() as usize,
+            image.height() as usize,
+            pixels,
+        ))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        let x = ((u.rem_euclid(1.0) * self.width as f64) as usize).min(self.width - 1);
+        let y = (((1.0 - v.rem_euclid(1.0)) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,94 @@
+use std::f64::consts::PI;
+
+use nalgebra::Complex;
+
+use crate::Rc;
+use crate::color::Color;
+use crate::hittable::HitRecord;
+use crate::spectrum::{Wavelengths, reflectance_to_rgb};
+use crate::texture::{SolidColor, Texture};
+
+// Wavelengths used to integrate the reflectance spectrum in RGB mode.
+const RGB_SAMPLES: usize = 32;
+
+// A transparent coating a few hundred nanometers thick, like a soap film or
+// an oil slick, whose reflections interfere.
+#[derive(Debug, Clone)]
+pub struct ThinFilm {
+    // Thickness in nanometers, read from the first channel
+    thickness: Rc<dyn Texture>,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        Self::from_texture(Rc::new(SolidColor::scalar(thickness)), ior)
+    }
+
+    pub fn from_texture(thickness: Rc<dyn Texture>, ior: f64) -> Self {
+        ThinFilm { thickness, ior }
+    }
+
+    // Reflectance of the coated surface for light arriving at `cos_theta`
+    // from a medium of index `outer`, with `substrate` giving the complex
+    // index of refraction below the film at each wavelength. In spectral mode
+    // this is exact at the wavelengths of the ray, in RGB mode it's the color
+    // of the reflected spectrum.
+    pub fn reflectance(
+        &self,
+        hit_record: &HitRecord,
+        cos_theta: f64,
+        outer: f64,
+        substrate: impl Fn(f64) -> Complex<f64>,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Color {
+        let thickness = self
+            .thickness
+            .value(hit_record.u, hit_record.v, &hit_record.p)
+            .x
+            .max(0.0);
+        let reflectance = |lambda: f64| {
+            self.reflectance_at(thickness, cos_theta, outer, substrate(lambda), lambda)
+        };
+        match wavelengths {
+            Some(wavelengths) => wavelengths.lambda.map(reflectance),
+            None => reflectance_to_rgb(reflectance, RGB_SAMPLES).map(|c| c.clamp(0.0, 1.0)),
+        }
+    }
+
+    // Airy reflectance of unpolarized light at a single wavelength.
+    fn reflectance_at(
+        &self,
+        thickness: f64,
+        cos_theta: f64,
+        outer: f64,
+        substrate: Complex<f64>,
+        lambda: f64,
+    ) -> f64 {
+        let cos1 = Complex::new(cos_theta.clamp(0.0, 1.0), 0.0);
+        let sin1_sq = Complex::new(1.0, 0.0) - cos1 * cos1;
+        let (n1, n2, n3) = (
+            Complex::new(outer, 0.0),
+            Complex::new(self.ior, 0.0),
+            substrate,
+        );
+        let cos_in =
+            |n: Complex<f64>| (Complex::new(1.0, 0.0) - sin1_sq * (n1 / n) * (n1 / n)).sqrt();
+        let (cos2, cos3) = (cos_in(n2), cos_in(n3));
+
+        // Phase difference between successive reflections inside the film
+        let phase = (Complex::i() * 4.0 * PI * n2 * cos2 * thickness / lambda).exp();
+        let airy = |r12: Complex<f64>, r23: Complex<f64>| {
+            ((r12 + r23 * phase) / (Complex::new(1.0, 0.0) + r12 * r23 * phase)).norm_sqr()
+        };
+        let s = |ni: Complex<f64>, ci: Complex<f64>, nj: Complex<f64>, cj: Complex<f64>| {
+            (ni * ci - nj * cj) / (ni * ci + nj * cj)
+        };
+        let p = |ni: Complex<f64>, ci: Complex<f64>, nj: Complex<f64>, cj: Complex<f64>| {
+            (nj * ci - ni * cj) / (nj * ci + ni * cj)
+        };
+        let rs = airy(s(n1, cos1, n2, cos2), s(n2, cos2, n3, cos3));
+        let rp = airy(p(n1, cos1, n2, cos2), p(n2, cos2, n3, cos3));
+        (0.5 * (rs + rp)).clamp(0.0, 1.0)
+    }
+}
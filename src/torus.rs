@@ -0,0 +1,149 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable, azimuth};
+use crate::material::Material;
+use crate::roots::{solve_quadratic, solve_quartic};
+
+// A torus lying in the xz-plane around the origin. `major_radius` is the
+// distance from the center to the middle of the tube and `minor_radius` the
+// radius of the tube. Use `Transformed` to place it in the world.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(major_radius: f64, minor_radius: f64, mat: Rc<dyn Material>) -> Self {
+        Torus {
+            major_radius: major_radius.max(0.0),
+            minor_radius: minor_radius.max(0.0),
+            mat,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let bound = big_r + small_r;
+
+        // Solve along a unit direction starting from where the ray enters the
+        // bounding sphere. Keeping the origin close to the torus keeps the
+        // quartic well conditioned for far away rays.
+        let length = ray.direction().magnitude();
+        let d = ray.direction() / length;
+        let enter = solve_quadratic(
+            1.0,
+            2.0 * ray.origin().dot(&d),
+            ray.origin().norm_squared() - bound * bound,
+        );
+        let &exit = enter.last()?;
+        if exit < 0.0 {
+            return None;
+        }
+        let shift = enter[0].max(0.0);
+        let o = ray.origin() + shift * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2)
+        let f = o.dot(&d);
+        let g = o.norm_squared() + big_r * big_r - small_r * small_r;
+        let four_r2 = 4.0 * big_r * big_r;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * g + 4.0 * f * f - four_r2 * (d.x * d.x + d.z * d.z),
+            4.0 * f * g - 2.0 * four_r2 * (o.x * d.x + o.z * d.z),
+            g * g - four_r2 * (o.x * o.x + o.z * o.z),
+        );
+
+        let t = roots
+            .into_iter()
+            .map(|t| (t + shift) / length)
+            .find(|t| t_range.contains(t))?;
+
+        let p = ray.at(t);
+        let ring = Vec3::new(p.x, 0.0, p.z);
+        let center = if ring.norm_squared() > 0.0 {
+            ring.normalize() * big_r
+        } else {
+            Vec3::zeros()
+        };
+        let outward_normal = (p - center) / small_r;
+        let v = (p.y.atan2(ring.magnitude() - big_r) + std::f64::consts::PI)
+            / (2.0 * std::f64::consts::PI);
+
+        Some(HitRecord::new(t, p, self.mat.clone(), outward_normal, ray).with_uv(azimuth(&p), v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bound = self.major_radius + self.minor_radius;
+        let extent = Point3::new(bound, self.minor_radius, bound);
+        Aabb::new(-extent, extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn torus() -> Torus {
+        Torus::new(2.0, 0.5, Rc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))))
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} vs {b}");
+    }
+
+    #[test]
+    fn hits_the_outside_of_the_tube() {
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus().hit(&ray, &(0.001..f64::INFINITY)).unwrap();
+        assert_near(hit.t, 2.5);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn hits_the_top_of_the_tube() {
+        let ray = Ray::new(Point3::new(0.0, 3.0, 2.0), Vec3::new(0.0, -2.0, 0.0));
+        let hit = torus().hit(&ray, &(0.001..f64::INFINITY)).unwrap();
+        // t is in units of the unnormalized direction
+        assert_near(hit.t, 1.25);
+        assert!((hit.normal - Vec3::y()).norm() < 1e-9);
+    }
+
+    #[test]
+    fn misses_through_the_hole() {
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus().hit(&ray, &(0.001..f64::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn leaves_the_tube_from_inside() {
+        let ray = Ray::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus().hit(&ray, &(0.001..f64::INFINITY)).unwrap();
+        assert_near(hit.t, 0.5);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn stays_accurate_for_far_rays() {
+        let ray = Ray::new(Point3::new(-1e5, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus().hit(&ray, &(0.001..f64::INFINITY)).unwrap();
+        assert!((hit.t - (1e5 - 2.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn respects_the_t_range() {
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = torus().hit(&ray, &(3.0..f64::INFINITY)).unwrap();
+        assert_near(hit.t, 3.5);
+        assert!(torus().hit(&ray, &(0.001..2.0)).is_none());
+    }
+}
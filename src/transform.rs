@@ -0,0 +1,83 @@
+use crate::Point3;
+use crate::Ray;
+use crate::Rc;
+use crate::Vec3;
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+
+pub type Matrix4 = nalgebra::Matrix4<f64>;
+pub type Matrix3 = nalgebra::Matrix3<f64>;
+
+// Places an object defined in its own object space into the world through an
+// affine transform, e.g.
+// `Matrix4::new_translation(&p) * Matrix4::new_rotation(axis_angle)`.
+#[derive(Debug, Clone)]
+pub struct Transformed {
+    object: Rc<dyn Hittable>,
+    matrix: Matrix4,
+    inverse: Matrix4,
+    normal_matrix: Matrix3,
+    bbox: Aabb,
+}
+
+impl Transformed {
+    pub fn new<T: Hittable + 'static>(object: T, matrix: Matrix4) -> Self {
+        Self::from_rc(Rc::new(object), matrix)
+    }
+
+    pub fn from_rc(object: Rc<dyn Hittable>, matrix: Matrix4) -> Self {
+        let inverse = matrix
+            .try_inverse()
+            .expect("object transform must be invertible");
+        let normal_matrix = inverse.fixed_view::<3, 3>(0, 0).transpose();
+
+        let object_bbox = object.bounding_box();
+        let bbox = if object_bbox.is_finite() {
+            Aabb::from_points(&object_bbox.corners().map(|c| transform_point(&matrix, &c)))
+        } else {
+            Aabb::infinite()
+        };
+
+        Transformed {
+            object,
+            matrix,
+            inverse,
+            normal_matrix,
+            bbox,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+}
+
+pub fn transform_point(matrix: &Matrix4, p: &Point3) -> Point3 {
+    matrix.transform_point(&(*p).into()).coords
+}
+
+pub fn transform_vector(matrix: &Matrix4, v: &Vec3) -> Vec3 {
+    matrix.transform_vector(v)
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, t_range: &std::ops::Range<f64>) -> Option<HitRecord> {
+        // The direction is not renormalized so t stays the same in both spaces.
+        let local_ray = Ray {
+            orig: transform_point(&self.inverse, &ray.origin()),
+            dir: transform_vector(&self.inverse, &ray.direction()),
+            ..*ray
+        };
+
+        let mut hit = self.object.hit(&local_ray, t_range)?;
+        hit.p = ray.at(hit.t);
+        hit.normal = (self.normal_matrix * hit.normal).normalize();
+        hit.dpdu = transform_vector(&self.matrix, &hit.dpdu);
+        hit.dpdv = transform_vector(&self.matrix, &hit.dpdv);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
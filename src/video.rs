@@ -0,0 +1,259 @@
+use crate::{animation::FrameSequence, hittable::Hittable, scene::Scene};
+use log::info;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoFormat {
+    // Looping animated GIF, quantized to 256 colors per frame and with frame
+    // delays in 1/100 s
+    #[cfg(feature = "image")]
+    Gif,
+    // Looping animated PNG
+    #[cfg(feature = "image")]
+    Apng,
+    // Uncompressed YUV4MPEG2 with 4:2:0 chroma, as read by ffmpeg, mpv and x264
+    Y4m,
+}
+
+impl VideoFormat {
+    // Picks the format from the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            #[cfg(feature = "image")]
+            "gif" => Some(VideoFormat::Gif),
+            #[cfg(feature = "image")]
+            "png" | "apng" => Some(VideoFormat::Apng),
+            "y4m" => Some(VideoFormat::Y4m),
+            _ => None,
+        }
+    }
+}
+
+// Writes 8 bit RGB frames of equal size one after another.
+pub enum VideoWriter {
+    #[cfg(feature = "image")]
+    Gif {
+        encoder: Box<image::codecs::gif::GifEncoder<BufWriter<File>>>,
+        width: u32,
+        height: u32,
+        fps: f64,
+    },
+    #[cfg(feature = "image")]
+    Apng(Box<png::Writer<BufWriter<File>>>),
+    Y4m {
+        file: BufWriter<File>,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl VideoWriter {
+    // `frame_count` frames are shown at `fps` frames per second.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        format: VideoFormat,
+        width: usize,
+        height: usize,
+        frame_count: usize,
+        fps: f64,
+    ) -> std::io::Result<Self> {
+        if !(fps > 0.0 && fps.is_finite()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid frame rate {fps}"),
+            ));
+        }
+        let file = BufWriter::new(File::create(path)?);
+        match format {
+            #[cfg(feature = "image")]
+            VideoFormat::Gif => {
+                let mut encoder = image::codecs::gif::GifEncoder::new_with_speed(file, 10);
+                encoder
+                    .set_repeat(image::codecs::gif::Repeat::Infinite)
+                    .map_err(std::io::Error::other)?;
+                Ok(VideoWriter::Gif {
+                    encoder: Box::new(encoder),
+                    width: width as u32,
+                    height: height as u32,
+                    fps,
+                })
+            }
+            #[cfg(feature = "image")]
+            VideoFormat::Apng => {
+                let mut encoder = png::Encoder::new(file, width as u32, height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                // 0 plays loops forever
+                encoder.set_animated(frame_count as u32, 0)?;
+                let (numerator, denominator) = frame_delay(fps);
+                encoder.set_frame_delay(numerator, denominator)?;
+                Ok(VideoWriter::Apng(Box::new(encoder.write_header()?)))
+            }
+            VideoFormat::Y4m => {
+                // Y4M streams need no frame count up front
+                let _ = frame_count;
+                let mut file = file;
+                let (numerator, denominator) = frame_rate(fps);
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{width} H{height} F{numerator}:{denominator} Ip A1:1 C420jpeg XCOLORRANGE=LIMITED"
+                )?;
+                Ok(VideoWriter::Y4m {
+                    file,
+                    width,
+                    height,
+                })
+            }
+        }
+    }
+
+    pub fn write_frame(&mut self, rgb: &[u8]) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "image")]
+            VideoWriter::Gif {
+                encoder,
+                width,
+                height,
+                fps,
+            } => {
+                let rgba = image::RgbaImage::from_fn(*width, *height, |x, y| {
+                    let i = 3 * (y * *width + x) as usize;
+                    image::Rgba([rgb[i], rgb[i + 1], rgb[i + 2], 255])
+                });
+                let delay = image::Delay::from_saturating_duration(
+                    std::time::Duration::from_secs_f64(fps.recip()),
+                );
+                encoder
+                    .encode_frame(image::Frame::from_parts(rgba, 0, 0, delay))
+                    .map_err(std::io::Error::other)
+            }
+            #[cfg(feature = "image")]
+            VideoWriter::Apng(writer) => Ok(writer.write_image_data(rgb)?),
+            VideoWriter::Y4m {
+                file,
+                width,
+                height,
+            } => {
+                file.write_all(b"FRAME\n")?;
+                let (y, u, v) = rgb_to_yuv420(rgb, *width, *height);
+                file.write_all(&y)?;
+                file.write_all(&u)?;
+                file.write_all(&v)
+            }
+        }
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        match self {
+            #[cfg(feature = "image")]
+            VideoWriter::Gif { encoder, .. } => {
+                // The trailer is written when the encoder is dropped
+                drop(encoder);
+                Ok(())
+            }
+            #[cfg(feature = "image")]
+            VideoWriter::Apng(writer) => Ok(writer.finish()?),
+            VideoWriter::Y4m { mut file, .. } => file.flush(),
+        }
+    }
+}
+
+// Renders the frame range of the animation into a single video file, timed by
+// its frames per second.
+pub fn render_video<W: Hittable, P: AsRef<Path>>(
+    path: P,
+    format: VideoFormat,
+    frames: &FrameSequence,
+    scene_at: impl FnMut(f64) -> Scene<W>,
+) -> std::io::Result<()> {
+    let animation = frames.animation;
+    let mut writer = None;
+    frames.render(scene_at, |_, camera, scene| {
+        let writer = match &mut writer {
+            Some(writer) => writer,
+            None => writer.insert(VideoWriter::create(
+                &path,
+                format,
+                camera.image_width(),
+                camera.image_height(),
+                animation.frames.len(),
+                animation.fps,
+            )?),
+        };
+        writer.write_frame(&camera.render_to_rgb(scene))
+    })?;
+    if let Some(writer) = writer {
+        writer.finish()?;
+    }
+    info!("Wrote {}", path.as_ref().display());
+    Ok(())
+}
+
+// APNG frame delay in seconds as a fraction of 16 bit numbers.
+#[cfg(feature = "image")]
+fn frame_delay(fps: f64) -> (u16, u16) {
+    if fps.fract() == 0.0 && fps <= u16::MAX as f64 {
+        (1, fps as u16)
+    } else {
+        (
+            (10000.0 / fps).round().clamp(1.0, u16::MAX as f64) as u16,
+            10000,
+        )
+    }
+}
+
+// Frame rate as a reduced fraction. The NTSC rates like 29.97 become their
+// exact value 30000/1001, others are rounded to thousandths.
+fn frame_rate(fps: f64) -> (u64, u64) {
+    let ntsc = fps * 1.001;
+    let (numerator, denominator) = if (ntsc - ntsc.round()).abs() < 1e-5 * ntsc {
+        (ntsc.round() as u64 * 1000, 1001)
+    } else {
+        ((fps * 1000.0).round().max(1.0) as u64, 1000)
+    };
+    let gcd = gcd(numerator, denominator);
+    (numerator / gcd, denominator / gcd)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Converts to BT.601 limited range YUV, averaging the chroma over 2x2 pixels.
+fn rgb_to_yuv420(rgb: &[u8], width: usize, height: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let pixel = |x: usize, y: usize| {
+        let i = 3 * (y * width + x);
+        (rgb[i] as f64, rgb[i + 1] as f64, rgb[i + 2] as f64)
+    };
+    let luma = |(r, g, b): (f64, f64, f64)| 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let cb = |(r, g, b): (f64, f64, f64)| 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let cr = |(r, g, b): (f64, f64, f64)| 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+
+    let y_plane = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| luma(pixel(x, y)).round() as u8)
+        .collect();
+
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut u_plane = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v_plane = Vec::with_capacity(chroma_width * chroma_height);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let block: Vec<_> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .map(|(dx, dy)| ((2 * cx + dx).min(width - 1), (2 * cy + dy).min(height - 1)))
+                .map(|(x, y)| pixel(x, y))
+                .collect();
+            let average =
+                |f: &dyn Fn((f64, f64, f64)) -> f64| block.iter().map(|&p| f(p)).sum::<f64>() / 4.0;
+            u_plane.push(average(&cb).round() as u8);
+            v_plane.push(average(&cr).round() as u8);
+        }
+    }
+    (y_plane, u_plane, v_plane)
+}